@@ -0,0 +1,81 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use solver::optimizer::optimize;
+use solver::vm::Program;
+use solver::{executor, Env, Expression};
+
+const SWEEP: &[&str] = &[
+    "a*x^2 + b*x + c",
+    "sin(x)^2 + cos(x)^2*exp(-x/a)",
+    "max(x, a, b)*hypot(x, c) + atan2(x, b)",
+    "series(k, 1, 20, x^k/k)",
+];
+
+/// Evaluates the expressions over a sweep of `x` with the tree walking
+/// interpreter, the compiled program and the compiled function, which is
+/// native code with the `jit` feature, and with the batch evaluation
+fn sweep(c: &mut Criterion) {
+    for text in SWEEP {
+        let expr = Expression::parse(text).unwrap();
+        let ast = optimize(expr.ast().clone());
+        let mut group = c.benchmark_group(*text);
+
+        group.bench_function("tree", |b| {
+            b.iter(|| {
+                (0..100).fold(0.0, |acc, i| {
+                    let x = i as f64 / 100.0;
+                    let lookup = |name: &str| match name {
+                        "x" => Some(x),
+                        "a" => Some(2.0),
+                        "b" => Some(-3.0),
+                        "c" => Some(1.0),
+                        _ => None,
+                    };
+                    acc + executor::eval(black_box(&ast), &lookup).unwrap()
+                })
+            })
+        });
+
+        let program = Program::compile(&ast);
+        group.bench_function("vm", |b| {
+            let mut registers = program.registers();
+            for (name, value) in [("a", 2.0), ("b", -3.0), ("c", 1.0)] {
+                if let Some(slot) = program.slot(name) {
+                    registers[slot] = value;
+                }
+            }
+            let x = program.slot("x").unwrap();
+            b.iter(|| {
+                (0..100).fold(0.0, |acc, i| {
+                    registers[x] = i as f64 / 100.0;
+                    acc + black_box(&program).run(&mut registers).unwrap()
+                })
+            })
+        });
+
+        let env = Env::from_iter([("a", 2.0), ("b", -3.0), ("c", 1.0)]);
+        let compiled = expr.compile("x", &env).unwrap();
+        group.bench_function("compiled", |b| {
+            b.iter(|| {
+                (0..100).fold(0.0, |acc, i| {
+                    acc + black_box(&compiled).call(i as f64 / 100.0)
+                })
+            })
+        });
+
+        let xs: Vec<f64> = (0..100).map(|i| i as f64 / 100.0).collect();
+        let mut out = vec![0.0; xs.len()];
+        let lookup = |name: &str| env.get(name);
+        group.bench_function("batch", |b| {
+            b.iter(|| {
+                program
+                    .eval_batch(&[("x", black_box(&xs))], &lookup, &mut out)
+                    .unwrap();
+                out.iter().sum::<f64>()
+            })
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, sweep);
+criterion_main!(benches);
@@ -0,0 +1,322 @@
+use std::fmt::{self, Display};
+use std::slice;
+use std::sync::Arc;
+
+use crate::context::NativeFunction;
+
+use crate::parser::{binary_op_info, unary_op_info, Assoc, OpInfo};
+use crate::tokens::Span;
+
+/// Expression tree node together with the source text it was parsed from
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub enum NodeKind {
+    Number(f64),
+    Const {
+        name: String,
+        value: f64,
+    },
+    Var(String),
+    Unary {
+        op: UnaryOp,
+        operand: Box<Node>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Node>,
+        rhs: Box<Node>,
+    },
+    Call {
+        name: String,
+        func: Arc<NativeFunction>,
+        args: Vec<Node>,
+    },
+    /// `op` over values of variable `var` from `from` to `to`, the variable is
+    /// bound in `body` only
+    Bound {
+        op: BoundOp,
+        var: String,
+        from: Box<Node>,
+        to: Box<Node>,
+        body: Box<Node>,
+    },
+    /// `body` with variable `var` bound to the value of `value`, which is
+    /// computed once. Made by the optimizer for common subexpressions, the
+    /// names of such variables can not be written in source
+    Let {
+        var: String,
+        value: Box<Node>,
+        body: Box<Node>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+/// Operators binding a variable: `series(k, 1, n, k^2)` and `prod(k, 1, n, k)`
+/// step it by one through integer bounds, `int(t, 0, x, exp(-t^2))` is the
+/// definite integral
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoundOp {
+    Series,
+    Prod,
+    Int,
+}
+
+impl Node {
+    pub fn new(kind: NodeKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// Whether variable `var` occurs in the node other than bound by
+    /// [`NodeKind::Bound`] or [`NodeKind::Let`]
+    pub fn depends_on(&self, var: &str) -> bool {
+        match &self.kind {
+            NodeKind::Number(_) | NodeKind::Const { .. } => false,
+            NodeKind::Var(name) => name == var,
+            NodeKind::Unary { operand, .. } => operand.depends_on(var),
+            NodeKind::Binary { lhs, rhs, .. } => lhs.depends_on(var) || rhs.depends_on(var),
+            NodeKind::Call { args, .. } => args.iter().any(|arg| arg.depends_on(var)),
+            NodeKind::Bound {
+                var: bound,
+                from,
+                to,
+                body,
+                ..
+            } => {
+                from.depends_on(var) || to.depends_on(var) || (bound != var && body.depends_on(var))
+            }
+            NodeKind::Let {
+                var: bound,
+                value,
+                body,
+            } => value.depends_on(var) || (bound != var && body.depends_on(var)),
+        }
+    }
+
+    /// Operator at the top of the node, negative numbers are printed the
+    /// same way as negation
+    fn op_info(&self) -> Option<OpInfo> {
+        match &self.kind {
+            NodeKind::Unary { op, .. } => Some(unary_op_info(*op)),
+            NodeKind::Binary { op, .. } => Some(binary_op_info(*op)),
+            NodeKind::Number(n) if n.is_sign_negative() => Some(unary_op_info(UnaryOp::Neg)),
+            _ => None,
+        }
+    }
+}
+
+impl BoundOp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BoundOp::Series => "series",
+            BoundOp::Prod => "prod",
+            BoundOp::Int => "int",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "series" => Some(BoundOp::Series),
+            "prod" => Some(BoundOp::Prod),
+            "int" => Some(BoundOp::Int),
+            _ => None,
+        }
+    }
+}
+
+impl BinaryOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Pow => "^",
+        }
+    }
+}
+
+/// Replaces variables `params` in `node` with `args`, the other nodes point to
+/// `span`. Variables bound inside `node` are renamed if one of `args` would be
+/// captured by them
+pub(crate) fn substitute(node: &Node, params: &[String], args: &[Node], span: Span) -> Node {
+    let sub = |node: &Node| Box::new(substitute(node, params, args, span));
+    let kind = match &node.kind {
+        NodeKind::Var(name) => match params.iter().position(|param| param == name) {
+            Some(i) => return args[i].clone(),
+            None => NodeKind::Var(name.clone()),
+        },
+        NodeKind::Unary { op, operand } => NodeKind::Unary {
+            op: *op,
+            operand: sub(operand),
+        },
+        NodeKind::Binary { op, lhs, rhs } => NodeKind::Binary {
+            op: *op,
+            lhs: sub(lhs),
+            rhs: sub(rhs),
+        },
+        NodeKind::Call {
+            name,
+            func,
+            args: call_args,
+        } => NodeKind::Call {
+            name: name.clone(),
+            func: func.clone(),
+            args: call_args
+                .iter()
+                .map(|arg| substitute(arg, params, args, span))
+                .collect(),
+        },
+        NodeKind::Bound {
+            op,
+            var,
+            from,
+            to,
+            body,
+        } => {
+            let (var, body) = substitute_scoped(var, body, params, args, span);
+            NodeKind::Bound {
+                op: *op,
+                var,
+                from: sub(from),
+                to: sub(to),
+                body: Box::new(body),
+            }
+        }
+        NodeKind::Let { var, value, body } => {
+            let (var, body) = substitute_scoped(var, body, params, args, span);
+            NodeKind::Let {
+                var,
+                value: sub(value),
+                body: Box::new(body),
+            }
+        }
+        kind => kind.clone(),
+    };
+    Node::new(kind, span)
+}
+
+/// Substitutes in `body` where `var` is bound, giving the possibly renamed
+/// variable along with the body
+fn substitute_scoped(
+    var: &String,
+    body: &Node,
+    params: &[String],
+    args: &[Node],
+    span: Span,
+) -> (String, Node) {
+    // the bound variable hides the parameter of the same name
+    let (inner_params, inner_args): (Vec<String>, Vec<Node>) = params
+        .iter()
+        .zip(args)
+        .filter(|(param, _)| *param != var && body.depends_on(param))
+        .map(|(param, arg)| (param.clone(), arg.clone()))
+        .unzip();
+    let mut bound = var.clone();
+    while inner_args.iter().any(|arg| arg.depends_on(&bound))
+        || (bound != *var && body.depends_on(&bound))
+    {
+        bound.push('\'');
+    }
+    let body = match bound == *var {
+        true => body.clone(),
+        false => {
+            let renamed = Node::new(NodeKind::Var(bound.clone()), body.span);
+            substitute(body, slice::from_ref(var), &[renamed], body.span)
+        }
+    };
+    let body = substitute(&body, &inner_params, &inner_args, span);
+    (bound, body)
+}
+
+/// Writes operand of `parent` operator wrapped in parentheses if it would be
+/// grouped differently otherwise. Prefix operators need no parentheses on the
+/// right side as they can only start a new operand there
+fn fmt_operand(
+    f: &mut fmt::Formatter<'_>,
+    node: &Node,
+    parent: OpInfo,
+    right: bool,
+) -> fmt::Result {
+    let parens = match node.op_info() {
+        Some(_) if right && matches!(node.kind, NodeKind::Unary { .. } | NodeKind::Number(_)) => {
+            false
+        }
+        Some(info) => {
+            info.prec < parent.prec
+                || (info.prec == parent.prec && (parent.assoc == Assoc::Left) == right)
+        }
+        None => false,
+    };
+    match parens {
+        true => write!(f, "({})", node),
+        false => write!(f, "{}", node),
+    }
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            NodeKind::Number(n) => write!(f, "{}", n),
+            NodeKind::Const { name, .. } | NodeKind::Var(name) => write!(f, "{}", name),
+            NodeKind::Unary { op, operand } => {
+                match op {
+                    UnaryOp::Neg => write!(f, "-")?,
+                }
+                fmt_operand(f, operand, unary_op_info(*op), true)
+            }
+            NodeKind::Binary { op, lhs, rhs } => {
+                fmt_operand(f, lhs, binary_op_info(*op), false)?;
+                match op {
+                    BinaryOp::Add | BinaryOp::Sub => write!(f, " {} ", op.symbol())?,
+                    _ => write!(f, "{}", op.symbol())?,
+                }
+                fmt_operand(f, rhs, binary_op_info(*op), true)
+            }
+            NodeKind::Call { name, args, .. } => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            NodeKind::Bound {
+                op,
+                var,
+                from,
+                to,
+                body,
+            } => write!(f, "{}({}, {}, {}, {})", op.name(), var, from, to, body),
+            // shared subexpressions are written out at every use
+            NodeKind::Let { var, value, body } => {
+                let expanded = substitute(
+                    body,
+                    slice::from_ref(var),
+                    slice::from_ref(value),
+                    body.span,
+                );
+                write!(f, "{}", expanded)
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use crate::solver_error::{Diagnostic, ErrorType};
+
+pub type DefinitionError = Diagnostic<DefinitionErrorType>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DefinitionErrorType {
+    InvalidDefinition,
+    InvalidName,
+    ReservedName,
+    NameConflict,
+    DuplicateParam,
+    RecursiveDefinition,
+}
+
+impl ErrorType for DefinitionErrorType {
+    fn code(&self) -> &'static str {
+        use DefinitionErrorType::*;
+        match self {
+            InvalidDefinition => "E501",
+            InvalidName => "E502",
+            ReservedName => "E503",
+            NameConflict => "E504",
+            DuplicateParam => "E505",
+            RecursiveDefinition => "E506",
+        }
+    }
+
+    fn message(&self) -> &'static str {
+        use DefinitionErrorType::*;
+        match self {
+            InvalidDefinition => "invalid definition",
+            InvalidName => "invalid name",
+            ReservedName => "can not redefine built-in",
+            NameConflict => "conflicting definition of",
+            DuplicateParam => "duplicate parameter",
+            RecursiveDefinition => "recursive definition uses",
+        }
+    }
+
+    fn help(&self) -> Option<&'static str> {
+        use DefinitionErrorType::*;
+        match self {
+            InvalidDefinition => Some("definitions look like `g = 9.80665` or `f(x, y) = x*y`"),
+            InvalidName => {
+                Some("names start with a letter or `_` followed by letters, digits or `_`")
+            }
+            NameConflict => Some("constants, functions and parameters need distinct names"),
+            RecursiveDefinition => {
+                Some("definitions can only use the constants and functions defined before them")
+            }
+            _ => None,
+        }
+    }
+}
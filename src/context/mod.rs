@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::ast::{substitute, Node};
+use crate::solver_error::{Error, SolverError};
+use crate::tokens::{Span, Token, TokenType};
+use crate::{executor, lexer, parser};
+use errors::{DefinitionError, DefinitionErrorType};
+
+pub use registry::{Arity, FunctionRegistry, NativeFn, NativeFunction};
+
+pub mod errors;
+mod registry;
+
+#[cfg(test)]
+mod tests;
+
+/// Name or expression together with its position in the definition text
+type Located<'a> = (&'a str, usize);
+
+/// Function defined by an expression of its parameters. Calls of it are
+/// replaced with the body when parsed, so the functions it calls are the ones
+/// defined at the time of its definition
+#[derive(Clone, Debug)]
+pub struct Function {
+    name: String,
+    params: Vec<String>,
+    source: String,
+    body: Node,
+}
+
+impl Function {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    /// Source of the body
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Body with the parameters replaced by `args`, the other nodes point to
+    /// the call at `span`
+    pub(crate) fn expand(&self, args: &[Node], span: Span) -> Node {
+        substitute(&self.body, &self.params, args, span)
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.params == other.params && self.source == other.source
+    }
+}
+
+/// User-defined constants, functions defined by expressions and native
+/// functions expressions can be parsed with, see
+/// [`Expression::parse_with`](crate::Expression::parse_with). Built-in names
+/// can not be redefined, a name can only have one kind of definition
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+    constants: HashMap<String, f64>,
+    functions: HashMap<String, Function>,
+    natives: FunctionRegistry,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn constant(&self, name: &str) -> Option<f64> {
+        self.constants.get(name).copied()
+    }
+
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+
+    pub fn native_function(&self, name: &str) -> Option<&Arc<NativeFunction>> {
+        self.natives.get(name)
+    }
+
+    pub fn define_constant(&mut self, name: &str, value: f64) -> Result<(), Error> {
+        self.define_constant_at((name, 0), value)
+    }
+
+    /// Defines function `name` of `params` computing `body`. The body may use
+    /// constants and functions defined earlier and variables bound when the
+    /// expression is evaluated
+    pub fn define_function(
+        &mut self,
+        name: &str,
+        params: &[&str],
+        body: &str,
+    ) -> Result<(), Error> {
+        let params: Vec<Located> = params.iter().map(|&param| (param, 0)).collect();
+        self.define_function_at((name, 0), &params, (body, 0))
+    }
+
+    /// Registers Rust closure as function `name`, e.g. one interpolating a
+    /// lookup table. Such functions can not be differentiated
+    pub fn register_function(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        func: impl Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    ) -> Result<(), Error> {
+        check_name((name, 0))?;
+        if self.constants.contains_key(name) || self.functions.contains_key(name) {
+            return Err(conflict((name, 0)).into());
+        }
+        self.natives.register(name, arity, func);
+        Ok(())
+    }
+
+    /// Defines constant like `g = 9.80665` or function like
+    /// `f(x, y) = x*y`, the constant is evaluated right away
+    pub fn define(&mut self, definition: &str) -> Result<(), Error> {
+        use DefinitionErrorType::*;
+        let Some((head, body)) = definition.split_once('=') else {
+            return Err(DefinitionError::new(0, definition, InvalidDefinition).into());
+        };
+        let body = (body, head.len() + 1);
+        let head = trimmed((head, 0));
+
+        let Some((name, params)) = head.0.split_once('(') else {
+            let value = executor::eval(&self.parse_body(head.0, body)?, &|_| None)
+                .map_err(|e| Error::from(e).shift(body.1))?;
+            return self.define_constant_at(head, value);
+        };
+        let Some(params) = params.trim_end().strip_suffix(')') else {
+            return Err(DefinitionError::new(head.1, head.0, InvalidDefinition).into());
+        };
+        let name = trimmed((name, head.1));
+        let params_pos = head.1 + head.0.find('(').unwrap() + 1;
+        let mut located = Vec::new();
+        if !params.trim().is_empty() {
+            let mut pos = params_pos;
+            for param in params.split(',') {
+                located.push(trimmed((param, pos)));
+                pos += param.len() + 1;
+            }
+        }
+        self.define_function_at(name, &located, body)
+    }
+
+    /// Token of a user-defined identifier
+    pub(crate) fn resolve(&self, name: &str) -> Option<TokenType<'_>> {
+        if let Some(value) = self.constant(name) {
+            return Some(TokenType::Const(value));
+        }
+        if let Some(function) = self.function(name) {
+            return Some(TokenType::UserFunc(function));
+        }
+        self.native_function(name).map(TokenType::Func)
+    }
+
+    fn define_constant_at(&mut self, name: Located, value: f64) -> Result<(), Error> {
+        check_name(name)?;
+        if self.functions.contains_key(name.0) || self.natives.contains(name.0) {
+            return Err(conflict(name).into());
+        }
+        self.constants.insert(name.0.to_string(), value);
+        Ok(())
+    }
+
+    fn define_function_at(
+        &mut self,
+        name: Located,
+        params: &[Located],
+        body: Located,
+    ) -> Result<(), Error> {
+        check_name(name)?;
+        if self.constants.contains_key(name.0) || self.natives.contains(name.0) {
+            return Err(conflict(name).into());
+        }
+        for (i, &param) in params.iter().enumerate() {
+            check_name(param)?;
+            if param.0 == name.0 || self.resolve(param.0).is_some() {
+                return Err(conflict(param).into());
+            }
+            if params[..i].iter().any(|other| other.0 == param.0) {
+                let error_type = DefinitionErrorType::DuplicateParam;
+                return Err(DefinitionError::new(param.1, param.0, error_type).into());
+            }
+        }
+        let function = Function {
+            name: name.0.to_string(),
+            params: params.iter().map(|param| param.0.to_string()).collect(),
+            source: body.0.trim().to_string(),
+            body: self.parse_body(name.0, body)?,
+        };
+        self.functions.insert(name.0.to_string(), function);
+        Ok(())
+    }
+
+    /// Parses body of the definition of `name`, which must not use `name`
+    fn parse_body(&self, name: &str, body: Located) -> Result<Node, Error> {
+        let mut tokens = Vec::new();
+        for token in lexer::analyse_with(body.0, self) {
+            let (pos, text) = match &token {
+                Ok(token) => (token.pos, token.text),
+                Err(error) => (error.span().pos, error.text()),
+            };
+            if text == name {
+                let error_type = DefinitionErrorType::RecursiveDefinition;
+                return Err(DefinitionError::new(body.1 + pos, name, error_type).into());
+            }
+            tokens.push(token.map_err(|e| Error::from(e).shift(body.1))?);
+        }
+        parser::parse(tokens.into_iter()).map_err(|e| Error::from(e).shift(body.1))
+    }
+}
+
+/// Checks that `name` is an identifier which is not built-in
+fn check_name(name: Located) -> Result<(), DefinitionError> {
+    use DefinitionErrorType::*;
+    let mut tokens = lexer::analyse(name.0);
+    let error_type = match (tokens.next(), tokens.next()) {
+        (
+            Some(Ok(Token {
+                token_type: TokenType::Var(_),
+                ..
+            })),
+            None,
+        ) => return Ok(()),
+        (Some(Ok(_)), None) => ReservedName,
+        _ => InvalidName,
+    };
+    Err(DefinitionError::new(name.1, name.0, error_type))
+}
+
+fn conflict(name: Located) -> DefinitionError {
+    DefinitionError::new(name.1, name.0, DefinitionErrorType::NameConflict)
+}
+
+/// Strips whitespace keeping the position pointing to the text
+fn trimmed((text, pos): Located) -> Located {
+    let start = text.len() - text.trim_start().len();
+    (text.trim(), pos + start)
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Display};
+use std::sync::{Arc, LazyLock};
+
+use crate::lexer::builtin_functions;
+
+/// Native implementation of a function, gets the evaluated arguments
+pub type NativeFn = dyn Fn(&[f64]) -> f64 + Send + Sync;
+
+static BUILTINS: LazyLock<FunctionRegistry> = LazyLock::new(builtin_functions);
+
+/// Number of arguments a function takes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arity {
+    Fixed(usize),
+    /// Variadic function taking at least this many arguments
+    AtLeast(usize),
+    /// Variadic function taking from the first to the second number of
+    /// arguments inclusive
+    Between(usize, usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, args: usize) -> bool {
+        match *self {
+            Arity::Fixed(n) => args == n,
+            Arity::AtLeast(n) => args >= n,
+            Arity::Between(min, max) => (min..=max).contains(&args),
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (prefix, n) = match *self {
+            Arity::Fixed(n) => (String::new(), n),
+            Arity::AtLeast(n) => ("at least ".to_string(), n),
+            Arity::Between(min, max) => (format!("{} to ", min), max),
+        };
+        let plural = if n == 1 { "" } else { "s" };
+        write!(f, "{}{} argument{}", prefix, n, plural)
+    }
+}
+
+/// Function implemented in Rust, possibly a closure capturing state
+#[derive(Clone)]
+pub struct NativeFunction {
+    name: String,
+    arity: Arity,
+    func: Arc<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: impl Into<String>,
+        arity: Arity,
+        func: impl Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            arity,
+            func: Arc::new(func),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    /// Calls the function, `args` must be accepted by its arity
+    pub fn call(&self, args: &[f64]) -> f64 {
+        (self.func)(args)
+    }
+}
+
+impl Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.arity == other.arity && Arc::ptr_eq(&self.func, &other.func)
+    }
+}
+
+/// Native functions by name. Expression trees share the functions with the
+/// registry they were parsed with
+#[derive(Clone, Debug, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Arc<NativeFunction>>,
+}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Built-in functions like `sin` and `max`
+    pub fn builtins() -> &'static FunctionRegistry {
+        &BUILTINS
+    }
+
+    /// Adds function replacing the one with the same name
+    pub fn register(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        func: impl Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    ) {
+        let function = NativeFunction::new(name, arity, func);
+        self.functions.insert(name.to_string(), Arc::new(function));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<NativeFunction>> {
+        self.functions.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.functions.keys().map(String::as_str)
+    }
+}
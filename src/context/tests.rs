@@ -0,0 +1,172 @@
+use super::*;
+use crate::solvers::{integral, root, IntegralMethod, RootMethod};
+use crate::{Env, Expression};
+
+fn context(definitions: &[&str]) -> Context {
+    let mut context = Context::new();
+    for definition in definitions {
+        context.define(definition).unwrap();
+    }
+    context
+}
+
+fn eval(text: &str, context: &Context) -> f64 {
+    let expr = Expression::parse_with(text, context).unwrap();
+    expr.eval(&Env::new()).unwrap()
+}
+
+#[test]
+fn it_works() {
+    let context = context(&[
+        "g = 9.80665",
+        "sigmoid(x) = 1/(1 + exp(-x))",
+        "f(x, y) = x*y + g",
+        "h() = 2*f(g, 1)",
+    ]);
+    assert_eq!(context.constant("g"), Some(9.80665));
+    assert_eq!(context.function("f").unwrap().params(), ["x", "y"]);
+
+    assert_eq!(eval("sigmoid(0)", &context), 0.5);
+    assert_eq!(eval("f(2, 3)", &context), 6.0 + 9.80665);
+    assert_eq!(eval("h()", &context), 4.0 * 9.80665);
+    // arguments are evaluated once per use of the parameter
+    assert_eq!(eval("f(sigmoid(0), -2)", &context), -1.0 + 9.80665);
+}
+
+#[test]
+fn programmatic_definitions() {
+    let mut context = Context::new();
+    context.define_constant("k", 2.0).unwrap();
+    context.define_function("scale", &["t"], "k*t").unwrap();
+    let expr = Expression::parse_with("scale(a) + k", &context).unwrap();
+    assert_eq!(expr.eval(&Env::new().with("a", 3.0)).unwrap(), 8.0);
+    assert_eq!(expr.to_string(), "k*a + k");
+
+    // definitions are expanded, so the expression works with any context
+    let derivative = expr.derivative("a").unwrap();
+    assert_eq!(derivative.eval(&Env::new()).unwrap(), 2.0);
+}
+
+#[test]
+fn bound_variables() {
+    let context = context(&[
+        "erf(x) = 2/sqrt(pi)*int(t, 0, x, exp(-t^2))",
+        "tri(n) = series(k, 1, n, k)",
+        "shadow(k) = k + series(k, 1, 3, k)",
+        "scaled(n) = series(k, 1, 3, k*n)",
+        "outer(k) = scaled(k)",
+    ]);
+    assert!(f64::abs(eval("erf(inf)", &context) - 1.0) < 0.000_000_001);
+    assert_eq!(eval("tri(4)", &context), 10.0);
+    assert_eq!(eval("shadow(10)", &context), 16.0);
+    // the argument is not captured by the bound variable of the body
+    assert_eq!(eval("outer(2)", &context), 12.0);
+}
+
+#[test]
+fn solvers() {
+    let context = context(&["sigmoid(x) = 1/(1 + exp(-x))", "half = 0.5"]);
+    let expr = Expression::parse_with("sigmoid(x) - half", &context).unwrap();
+    let env = Env::new();
+    let f = expr.closure("x", &env).unwrap();
+    let r = root(&f, -1.0, 2.0, 0.000_000_1, None, RootMethod::default());
+    assert!(f64::abs(r.ok().unwrap()) < 0.000_001);
+    let i = integral(&f, -1.0, 1.0, 0.000_000_1, None, IntegralMethod::default());
+    assert!(f64::abs(i.ok().unwrap()) < 0.000_001);
+}
+
+#[test]
+fn errors() {
+    use DefinitionErrorType::*;
+    let mut context = context(&["c = 1", "f(x) = x"]);
+    let cases = [
+        ("f(x) = f(x - 1)", RecursiveDefinition, "f", 7),
+        ("g(x) = 2*g", RecursiveDefinition, "g", 9),
+        ("c = c + 1", RecursiveDefinition, "c", 4),
+        ("sin(x) = x", ReservedName, "sin", 0),
+        ("pi = 3", ReservedName, "pi", 0),
+        ("c(x) = x", NameConflict, "c", 0),
+        ("f = 2", NameConflict, "f", 0),
+        ("g(x, c) = x", NameConflict, "c", 5),
+        ("g(x, g) = x", NameConflict, "g", 5),
+        ("g(x,  x) = x", DuplicateParam, "x", 6),
+        ("g(2x) = x", InvalidName, "2x", 2),
+        ("g(x = x", InvalidDefinition, "g(x", 0),
+        ("g", InvalidDefinition, "g", 0),
+    ];
+    for (definition, error_type, text, pos) in cases {
+        let error = context.define(definition).unwrap_err();
+        let Error::Definition(error) = &error else {
+            panic!("{}: unexpected error {}", definition, error);
+        };
+        assert_eq!(error.error_type(), &error_type, "{}", definition);
+        assert_eq!(error.text(), text, "{}", definition);
+        assert_eq!(error.span().pos, pos, "{}", definition);
+    }
+
+    // errors of the body point to the definition text
+    let error = context.define("g(x) = x +").unwrap_err();
+    assert_eq!(error.code(), "E202");
+    assert_eq!(error.span().pos, 9);
+    let error = context.define("k = 2*y").unwrap_err();
+    assert_eq!(error.code(), "E301");
+    assert_eq!(error.span().pos, 6);
+
+    let error = Expression::parse_with("f(1, 2)", &context).unwrap_err();
+    assert_eq!(error.code(), "E204");
+    assert_eq!(error.help(), Some("`f` takes 1 argument"));
+}
+
+#[test]
+fn native_functions() {
+    let mut context = context(&["k = 2"]);
+    // closure interpolating a lookup table
+    let table = [(0.0, 0.0), (1.0, 10.0), (2.0, 40.0)];
+    context
+        .register_function("lookup", Arity::Fixed(1), move |args| {
+            let i = table
+                .partition_point(|&(x, _)| x <= args[0])
+                .clamp(1, table.len() - 1);
+            let ((x0, y0), (x1, y1)) = (table[i - 1], table[i]);
+            y0 + (y1 - y0) * (args[0] - x0) / (x1 - x0)
+        })
+        .unwrap();
+    context
+        .register_function("total", Arity::AtLeast(1), |args| args.iter().sum())
+        .unwrap();
+    context.define("twice(x) = k*lookup(x)").unwrap();
+
+    assert_eq!(eval("lookup(1.5)", &context), 25.0);
+    assert_eq!(eval("twice(0.5)", &context), 10.0);
+    assert_eq!(eval("total(1, 2, 3, 4, 5, 6)", &context), 21.0);
+    assert_eq!(eval("total(k)", &context), 2.0);
+
+    let error = Expression::parse_with("total()", &context).unwrap_err();
+    assert_eq!(error.code(), "E204");
+    assert_eq!(error.help(), Some("`total` takes at least 1 argument"));
+
+    context
+        .register_function("scaled", Arity::Between(1, 2), |args| {
+            args[0] * args.get(1).unwrap_or(&10.0)
+        })
+        .unwrap();
+    assert_eq!(eval("scaled(2) + scaled(2, 3)", &context), 26.0);
+    let error = Expression::parse_with("scaled(1, 2, 3)", &context).unwrap_err();
+    assert_eq!(error.help(), Some("`scaled` takes 1 to 2 arguments"));
+
+    let expr = Expression::parse_with("lookup(x)", &context).unwrap();
+    assert_eq!(expr.derivative("x").unwrap_err().code(), "E401");
+
+    let error = context
+        .register_function("sin", Arity::Fixed(1), |args| args[0])
+        .unwrap_err();
+    assert_eq!(error.code(), "E503");
+    let error = context
+        .register_function("k", Arity::Fixed(1), |args| args[0])
+        .unwrap_err();
+    assert_eq!(error.code(), "E504");
+    assert_eq!(context.define("lookup = 1").unwrap_err().code(), "E504");
+
+    fn shareable<T: Send + Sync>(_: &T) {}
+    shareable(&expr);
+}
@@ -0,0 +1,24 @@
+use crate::solver_error::{Diagnostic, ErrorType};
+
+pub type DiffError = Diagnostic<DiffErrorType>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffErrorType {
+    NotDifferentiable,
+}
+
+impl ErrorType for DiffErrorType {
+    fn code(&self) -> &'static str {
+        use DiffErrorType::*;
+        match self {
+            NotDifferentiable => "E401",
+        }
+    }
+
+    fn message(&self) -> &'static str {
+        use DiffErrorType::*;
+        match self {
+            NotDifferentiable => "no derivative known for function",
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use std::slice;
+
+use crate::ast::{substitute, BinaryOp, BoundOp, Node, NodeKind, UnaryOp};
+use crate::context::FunctionRegistry;
+use crate::tokens::Span;
+use errors::{DiffError, DiffErrorType};
+
+pub use simplify::simplify;
+
+pub mod errors;
+mod simplify;
+
+#[cfg(test)]
+mod tests;
+
+/// Differentiates expression tree with respect to `var`. The result is not
+/// simplified and its nodes point to the source of the differentiated ones
+pub fn diff(node: &Node, var: &str) -> Result<Node, DiffError> {
+    let b = Builder { span: node.span };
+    let d = |node: &Node| diff(node, var);
+
+    Ok(match &node.kind {
+        NodeKind::Number(_) | NodeKind::Const { .. } => b.num(0.0),
+        NodeKind::Var(name) => b.num(if name == var { 1.0 } else { 0.0 }),
+        NodeKind::Unary {
+            op: UnaryOp::Neg,
+            operand,
+        } => b.neg(d(operand)?),
+        NodeKind::Binary { op, lhs, rhs } => {
+            let (u, v) = (lhs.as_ref().clone(), rhs.as_ref().clone());
+            match op {
+                BinaryOp::Add => b.add(d(lhs)?, d(rhs)?),
+                BinaryOp::Sub => b.sub(d(lhs)?, d(rhs)?),
+                BinaryOp::Mul => b.add(b.mul(d(lhs)?, v), b.mul(u, d(rhs)?)),
+                BinaryOp::Div => b.div(
+                    b.sub(b.mul(d(lhs)?, v.clone()), b.mul(u, d(rhs)?)),
+                    b.pow(v, b.num(2.0)),
+                ),
+                BinaryOp::Pow => power(&b, node, lhs, rhs, var)?,
+            }
+        }
+        NodeKind::Call { name, args, .. } => call(&b, node, name, args, var)?,
+        NodeKind::Bound { .. } => bound(&b, node, var)?,
+        NodeKind::Let {
+            var: bound,
+            value,
+            body,
+        } => d(&substitute(
+            body,
+            slice::from_ref(bound),
+            slice::from_ref(value),
+            node.span,
+        ))?,
+    })
+}
+
+/// Derivative of `u^v` given as `node`
+fn power(b: &Builder, node: &Node, u: &Node, v: &Node, var: &str) -> Result<Node, DiffError> {
+    let (u_var, v_var) = (u.depends_on(var), v.depends_on(var));
+    Ok(match (u_var, v_var) {
+        (_, false) => b.mul(
+            b.mul(v.clone(), b.pow(u.clone(), b.sub(v.clone(), b.num(1.0)))),
+            diff(u, var)?,
+        ),
+        (false, true) if matches!(&u.kind, NodeKind::Const { name, .. } if name == "e") => {
+            b.mul(node.clone(), diff(v, var)?)
+        }
+        (false, true) => b.mul(
+            b.mul(node.clone(), b.call("ln", vec![u.clone()])),
+            diff(v, var)?,
+        ),
+        (true, true) => b.mul(
+            node.clone(),
+            b.add(
+                b.mul(diff(v, var)?, b.call("ln", vec![u.clone()])),
+                b.div(b.mul(v.clone(), diff(u, var)?), u.clone()),
+            ),
+        ),
+    })
+}
+
+/// Chain rule for every function known to the lexer
+fn call(b: &Builder, node: &Node, name: &str, args: &[Node], var: &str) -> Result<Node, DiffError> {
+    let arg = |i: usize| args[i].clone();
+    let d = |i: usize| diff(&args[i], var);
+    let sq = |node: Node| b.pow(node, b.num(2.0));
+    let total = |terms: Vec<Node>| terms.into_iter().reduce(|a, c| b.add(a, c)).unwrap();
+    let all = || (0..args.len()).map(d).collect::<Result<Vec<_>, _>>();
+
+    Ok(match name {
+        "abs" => b.mul(b.call("sign", vec![arg(0)]), d(0)?),
+        "acos" => b.neg(b.div(d(0)?, b.call("sqrt", vec![b.sub(b.num(1.0), sq(arg(0)))]))),
+        "acosh" => b.div(d(0)?, b.call("sqrt", vec![b.sub(sq(arg(0)), b.num(1.0))])),
+        "asin" => b.div(d(0)?, b.call("sqrt", vec![b.sub(b.num(1.0), sq(arg(0)))])),
+        "asinh" => b.div(d(0)?, b.call("sqrt", vec![b.add(sq(arg(0)), b.num(1.0))])),
+        "atan" => b.div(d(0)?, b.add(b.num(1.0), sq(arg(0)))),
+        "atan2" => b.div(
+            b.sub(b.mul(arg(1), d(0)?), b.mul(arg(0), d(1)?)),
+            b.add(sq(arg(0)), sq(arg(1))),
+        ),
+        "atanh" => b.div(d(0)?, b.sub(b.num(1.0), sq(arg(0)))),
+        "cbrt" => b.div(d(0)?, b.mul(b.num(3.0), sq(node.clone()))),
+        "ceil" | "floor" | "round" | "trunc" | "sign" => b.num(0.0),
+        "clamp" => {
+            let max = b.call("max", vec![arg(0), arg(1)]);
+            diff(&b.call("min", vec![max, arg(2)]), var)?
+        }
+        "cos" => b.neg(b.mul(b.call("sin", vec![arg(0)]), d(0)?)),
+        "cosh" => b.mul(b.call("sinh", vec![arg(0)]), d(0)?),
+        "exp" => b.mul(node.clone(), d(0)?),
+        "exp2" => b.mul(b.mul(node.clone(), b.call("ln", vec![b.num(2.0)])), d(0)?),
+        "fract" => d(0)?,
+        "hypot" => {
+            let terms = (0..args.len()).map(|i| Ok(b.mul(arg(i), d(i)?)));
+            b.div(
+                total(terms.collect::<Result<_, DiffError>>()?),
+                node.clone(),
+            )
+        }
+        "ln" => b.div(d(0)?, arg(0)),
+        "log2" => b.div(d(0)?, b.mul(arg(0), b.call("ln", vec![b.num(2.0)]))),
+        "log10" => b.div(d(0)?, b.mul(arg(0), b.call("ln", vec![b.num(10.0)]))),
+        "max" | "min" if args.len() == 1 => d(0)?,
+        // max(u, v, w) = max(max(u, v), w)
+        "max" | "min" if args.len() > 2 => {
+            let nested = args[1..]
+                .iter()
+                .fold(arg(0), |acc, a| b.call(name, vec![acc, a.clone()]));
+            diff(&nested, var)?
+        }
+        // max(u, v) = (u + v + |u - v|) / 2, min(u, v) = (u + v - |u - v|) / 2
+        "max" | "min" => {
+            let (du, dv) = (d(0)?, d(1)?);
+            let sign = b.call("sign", vec![b.sub(arg(0), arg(1))]);
+            let abs = b.mul(sign, b.sub(du.clone(), dv.clone()));
+            let sum = match name {
+                "max" => b.add(b.add(du, dv), abs),
+                _ => b.sub(b.add(du, dv), abs),
+            };
+            b.div(sum, b.num(2.0))
+        }
+        "mean" => b.div(total(all()?), b.num(args.len() as f64)),
+        "median" => {
+            // derivative of the argument equal to `median`, the mean of those
+            // with the same value
+            let select = |median: Node| {
+                let equal = |i: usize| {
+                    let distance = b.call("abs", vec![b.sub(arg(i), median.clone())]);
+                    let different = b.call("min", vec![distance, b.num(1.0)]);
+                    b.sub(b.num(1.0), b.call("ceil", vec![different]))
+                };
+                let terms = (0..args.len()).map(|i| Ok(b.mul(equal(i), d(i)?)));
+                Ok::<_, DiffError>(b.div(
+                    total(terms.collect::<Result<_, DiffError>>()?),
+                    total((0..args.len()).map(equal).collect()),
+                ))
+            };
+            match args.len() % 2 {
+                1 => select(node.clone())?,
+                // the two middle values are the medians with an infinity
+                // added below or above
+                _ => {
+                    let with = |bound: f64| {
+                        let mut args = args.to_vec();
+                        args.push(b.num(bound));
+                        b.call("median", args)
+                    };
+                    let lower = select(with(f64::NEG_INFINITY))?;
+                    let upper = select(with(f64::INFINITY))?;
+                    b.div(b.add(lower, upper), b.num(2.0))
+                }
+            }
+        }
+        "mul_add" => b.add(b.add(b.mul(d(0)?, arg(1)), b.mul(arg(0), d(1)?)), d(2)?),
+        "pow" => power(b, node, &args[0], &args[1], var)?,
+        "recip" => b.neg(b.div(d(0)?, sq(arg(0)))),
+        "sin" => b.mul(b.call("cos", vec![arg(0)]), d(0)?),
+        "sinh" => b.mul(b.call("cosh", vec![arg(0)]), d(0)?),
+        "sqrt" => b.div(d(0)?, b.mul(b.num(2.0), node.clone())),
+        // stdev' = sum((u_i - mean)*u_i') / ((n - 1)*stdev)
+        "stdev" => {
+            let mean = b.call("mean", args.to_vec());
+            let terms = (0..args.len()).map(|i| Ok(b.mul(b.sub(arg(i), mean.clone()), d(i)?)));
+            let n = b.num(args.len() as f64 - 1.0);
+            b.div(
+                total(terms.collect::<Result<_, DiffError>>()?),
+                b.mul(n, node.clone()),
+            )
+        }
+        "sum" => total(all()?),
+        "tan" => b.div(d(0)?, sq(b.call("cos", vec![arg(0)]))),
+        "tanh" => b.mul(b.sub(b.num(1.0), sq(node.clone())), d(0)?),
+        // linear functions
+        "toDeg" | "toRad" => b.call(name, vec![d(0)?]),
+        _ => {
+            return Err(DiffError::new(
+                node.span.pos,
+                name,
+                DiffErrorType::NotDifferentiable,
+            ))
+        }
+    })
+}
+
+/// Derivative of a bound operator given as `node`. Bounds of `series` and
+/// `prod` are integers, so they do not change the derivative, `int` follows
+/// the Leibniz integral rule
+fn bound(b: &Builder, node: &Node, var: &str) -> Result<Node, DiffError> {
+    let NodeKind::Bound {
+        op,
+        var: bound,
+        from,
+        to,
+        body,
+    } = &node.kind
+    else {
+        unreachable!("only called for bound operators");
+    };
+    // the bound variable hides `var` in the body
+    let constant_body = bound == var || !body.depends_on(var);
+    let with_body = |body: Node| {
+        let kind = NodeKind::Bound {
+            op: *op,
+            var: bound.clone(),
+            from: from.clone(),
+            to: to.clone(),
+            body: Box::new(body),
+        };
+        Node::new(kind, b.span)
+    };
+    let d_body = || match constant_body {
+        true => Ok(b.num(0.0)),
+        false => diff(body, var),
+    };
+
+    Ok(match op {
+        BoundOp::Series => with_body(d_body()?),
+        BoundOp::Prod if constant_body => b.num(0.0),
+        BoundOp::Prod => {
+            return Err(DiffError::new(
+                node.span.pos,
+                op.name(),
+                DiffErrorType::NotDifferentiable,
+            ))
+        }
+        BoundOp::Int => {
+            let at = |limit: &Node| {
+                substitute(body, slice::from_ref(bound), slice::from_ref(limit), b.span)
+            };
+            let limits = b.sub(
+                b.mul(at(to), diff(to, var)?),
+                b.mul(at(from), diff(from, var)?),
+            );
+            b.add(limits, with_body(d_body()?))
+        }
+    })
+}
+
+/// Creates nodes of the derivative pointing to the differentiated node source
+struct Builder {
+    span: Span,
+}
+
+impl Builder {
+    fn num(&self, n: f64) -> Node {
+        Node::new(NodeKind::Number(n), self.span)
+    }
+
+    fn neg(&self, operand: Node) -> Node {
+        let kind = NodeKind::Unary {
+            op: UnaryOp::Neg,
+            operand: Box::new(operand),
+        };
+        Node::new(kind, self.span)
+    }
+
+    fn binary(&self, op: BinaryOp, lhs: Node, rhs: Node) -> Node {
+        let kind = NodeKind::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+        Node::new(kind, self.span)
+    }
+
+    fn add(&self, lhs: Node, rhs: Node) -> Node {
+        self.binary(BinaryOp::Add, lhs, rhs)
+    }
+
+    fn sub(&self, lhs: Node, rhs: Node) -> Node {
+        self.binary(BinaryOp::Sub, lhs, rhs)
+    }
+
+    fn mul(&self, lhs: Node, rhs: Node) -> Node {
+        self.binary(BinaryOp::Mul, lhs, rhs)
+    }
+
+    fn div(&self, lhs: Node, rhs: Node) -> Node {
+        self.binary(BinaryOp::Div, lhs, rhs)
+    }
+
+    fn pow(&self, lhs: Node, rhs: Node) -> Node {
+        self.binary(BinaryOp::Pow, lhs, rhs)
+    }
+
+    /// Call of a built-in function
+    fn call(&self, name: &str, args: Vec<Node>) -> Node {
+        let Some(func) = FunctionRegistry::builtins().get(name) else {
+            unreachable!("derivatives only call built-in functions");
+        };
+        let kind = NodeKind::Call {
+            name: name.into(),
+            func: func.clone(),
+            args,
+        };
+        Node::new(kind, self.span)
+    }
+}
@@ -0,0 +1,224 @@
+use crate::ast::{BinaryOp, BoundOp, Node, NodeKind, UnaryOp};
+use crate::tokens::Span;
+
+/// Folds arithmetic on numbers and removes identities like `x*1`, `x + 0`,
+/// `x^1` and sums or integrals of zero, bottom up. Multiplication by zero is
+/// dropped even if the other operand is not finite, and `u/u` is `1` even
+/// where `u` is zero
+pub fn simplify(node: Node) -> Node {
+    let Node { kind, span } = node;
+    match kind {
+        NodeKind::Unary {
+            op: UnaryOp::Neg,
+            operand,
+        } => negate(simplify(*operand), span),
+        NodeKind::Binary { op, lhs, rhs } => binary(op, simplify(*lhs), simplify(*rhs), span),
+        NodeKind::Call { name, func, args } => {
+            let kind = NodeKind::Call {
+                name,
+                func,
+                args: args.into_iter().map(simplify).collect(),
+            };
+            Node::new(kind, span)
+        }
+        NodeKind::Bound {
+            op,
+            var,
+            from,
+            to,
+            body,
+        } => {
+            let body = simplify(*body);
+            if op != BoundOp::Prod && number(&body) == Some(0.0) {
+                return body;
+            }
+            let kind = NodeKind::Bound {
+                op,
+                var,
+                from: Box::new(simplify(*from)),
+                to: Box::new(simplify(*to)),
+                body: Box::new(body),
+            };
+            Node::new(kind, span)
+        }
+        kind => Node::new(kind, span),
+    }
+}
+
+fn number(node: &Node) -> Option<f64> {
+    match node.kind {
+        NodeKind::Number(n) => Some(n),
+        _ => None,
+    }
+}
+
+fn negate(operand: Node, span: Span) -> Node {
+    match operand.kind {
+        NodeKind::Number(n) => Node::new(NodeKind::Number(-n), span),
+        NodeKind::Unary {
+            op: UnaryOp::Neg,
+            operand,
+        } => *operand,
+        // `-(2*x)` is `-2*x`
+        NodeKind::Binary {
+            op: BinaryOp::Mul,
+            lhs,
+            rhs,
+        } if number(&lhs).is_some() => {
+            let coefficient = Node::new(NodeKind::Number(-number(&lhs).unwrap()), lhs.span);
+            make(BinaryOp::Mul, coefficient, *rhs, span)
+        }
+        kind => {
+            let kind = NodeKind::Unary {
+                op: UnaryOp::Neg,
+                operand: Box::new(Node::new(kind, operand.span)),
+            };
+            Node::new(kind, span)
+        }
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Node, rhs: Node, span: Span) -> Node {
+    use BinaryOp::*;
+    let num = |n: f64| Node::new(NodeKind::Number(n), span);
+
+    if let Some(node) = cancel(op, &lhs, &rhs, span) {
+        return node;
+    }
+    // `x + -y` is `x - y`, `x - -2*y` is `x + 2*y`
+    if let (Add | Sub, None, Some(positive)) = (op, number(&rhs), strip_minus(&rhs)) {
+        let flipped = if op == Add { Sub } else { Add };
+        return binary(flipped, lhs, positive, span);
+    }
+    match (op, number(&lhs), number(&rhs)) {
+        (Add, Some(a), Some(b)) => num(a + b),
+        (Sub, Some(a), Some(b)) => num(a - b),
+        (Mul, Some(a), Some(b)) => num(a * b),
+        // keep `1/3` and `2^0.5` readable
+        (Pow, Some(a), Some(b)) if a.powf(b).fract() == 0.0 => num(a.powf(b)),
+
+        (Add, Some(0.0), _) => rhs,
+        (Add | Sub, _, Some(0.0)) => lhs,
+        (Sub, Some(0.0), _) => negate(rhs, span),
+        (Mul, Some(0.0), _) | (Mul, _, Some(0.0)) => num(0.0),
+        (Div, Some(0.0), _) => num(0.0),
+        (Mul, Some(1.0), _) => rhs,
+        (Mul | Div, _, Some(1.0)) => lhs,
+        (Mul, Some(-1.0), _) => negate(rhs, span),
+        (Mul | Div, _, Some(-1.0)) => negate(lhs, span),
+        (Pow, _, Some(1.0)) => lhs,
+        (Pow, _, Some(0.0)) => num(1.0),
+        (Pow, Some(1.0), _) => num(1.0),
+
+        // coefficients go first and are merged: `x*2` is `2*x`,
+        // `2*(3*x)` is `6*x`
+        (Mul, None, Some(_)) => binary(Mul, rhs, lhs, span),
+        (Mul, Some(a), None) => match rhs.kind {
+            // `3*(-x)` is `-3*x`
+            NodeKind::Unary {
+                op: UnaryOp::Neg,
+                operand,
+            } => binary(Mul, num(-a), *operand, span),
+            NodeKind::Binary {
+                op: Mul,
+                lhs: inner,
+                rhs: rest,
+            } if number(&inner).is_some() => {
+                binary(Mul, num(a * number(&inner).unwrap()), *rest, span)
+            }
+            kind => make(Mul, lhs, Node::new(kind, rhs.span), span),
+        },
+
+        (Add, _, Some(n)) if n < 0.0 => make(Sub, lhs, num(-n), span),
+        (Sub, _, Some(n)) if n < 0.0 => make(Add, lhs, num(-n), span),
+        _ => match (op, lhs.kind, rhs.kind) {
+            // negation goes to the leftmost factor, `x*(-y)` is `-x*y`
+            (
+                Mul | Div,
+                lhs_kind,
+                NodeKind::Unary {
+                    op: UnaryOp::Neg,
+                    operand,
+                },
+            ) => binary(
+                op,
+                negate(Node::new(lhs_kind, lhs.span), span),
+                *operand,
+                span,
+            ),
+            // `x*(2*y)` is `2*x*y`
+            (
+                Mul,
+                lhs_kind,
+                NodeKind::Binary {
+                    op: Mul,
+                    lhs: inner,
+                    rhs: rest,
+                },
+            ) if number(&inner).is_some() => {
+                let lhs = binary(Mul, *inner, Node::new(lhs_kind, lhs.span), span);
+                binary(Mul, lhs, *rest, span)
+            }
+            (op, lhs_kind, rhs_kind) => make(
+                op,
+                Node::new(lhs_kind, lhs.span),
+                Node::new(rhs_kind, rhs.span),
+                span,
+            ),
+        },
+    }
+}
+
+/// `node` without its leading minus if it has one: `-x`, `-2*x`, `-x*y` or
+/// `-x/y`
+fn strip_minus(node: &Node) -> Option<Node> {
+    match &node.kind {
+        NodeKind::Unary {
+            op: UnaryOp::Neg,
+            operand,
+        } => Some(operand.as_ref().clone()),
+        NodeKind::Binary {
+            op: op @ (BinaryOp::Mul | BinaryOp::Div),
+            lhs,
+            rhs,
+        } => {
+            let lhs = match number(lhs) {
+                Some(n) if n < 0.0 => Node::new(NodeKind::Number(-n), lhs.span),
+                Some(_) => return None,
+                None => strip_minus(lhs)?,
+            };
+            Some(binary(*op, lhs, rhs.as_ref().clone(), node.span))
+        }
+        _ => None,
+    }
+}
+
+/// `u/u` is `1`, `a/u*u` and `u*(a/u)` are `a`. Subexpressions are compared
+/// by their text
+fn cancel(op: BinaryOp, lhs: &Node, rhs: &Node, span: Span) -> Option<Node> {
+    let same = |a: &Node, b: &Node| a.to_string() == b.to_string();
+    let dividend = |node: &Node, divisor: &Node| match &node.kind {
+        NodeKind::Binary {
+            op: BinaryOp::Div,
+            lhs,
+            rhs,
+        } if same(rhs, divisor) => Some(lhs.as_ref().clone()),
+        _ => None,
+    };
+    match op {
+        BinaryOp::Div if number(lhs).is_none() && same(lhs, rhs) => {
+            Some(Node::new(NodeKind::Number(1.0), span))
+        }
+        BinaryOp::Mul => dividend(lhs, rhs).or_else(|| dividend(rhs, lhs)),
+        _ => None,
+    }
+}
+
+fn make(op: BinaryOp, lhs: Node, rhs: Node, span: Span) -> Node {
+    let kind = NodeKind::Binary {
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+    };
+    Node::new(kind, span)
+}
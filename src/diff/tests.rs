@@ -0,0 +1,156 @@
+use super::*;
+use crate::solver_error::SolverError;
+use crate::{Env, Expression};
+
+fn derivative(text: &str) -> String {
+    Expression::parse(text)
+        .unwrap()
+        .derivative("x")
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn it_works() {
+    assert_eq!(derivative("x^2*sin(x)"), "2*x*sin(x) + x^2*cos(x)");
+    assert_eq!(derivative("3*x + 2"), "3");
+    assert_eq!(derivative("x^3 - 2*x"), "3*x^2 - 2");
+    assert_eq!(derivative("-cos(x)"), "sin(x)");
+    assert_eq!(derivative("ln(x)"), "1/x");
+    assert_eq!(derivative("exp(2*x)"), "2*exp(2*x)");
+    assert_eq!(derivative("a*x"), "a");
+    assert_eq!(derivative("e^(2*x)"), "2*e^(2*x)");
+    assert_eq!(derivative("pi"), "0");
+    assert_eq!(derivative("x^x"), "x^x*(ln(x) + 1)");
+    assert_eq!(derivative("ln(x)/x"), "(1 - ln(x))/x^2");
+    assert_eq!(derivative("x - 3*cos(x)"), "1 + 3*sin(x)");
+    assert_eq!(derivative("sin(x)*cos(x)"), "cos(x)*cos(x) - sin(x)*sin(x)");
+}
+
+#[test]
+fn simplification() {
+    let simplified = |text: &str| {
+        let expr = Expression::parse(text).unwrap();
+        simplify(expr.ast().clone()).to_string()
+    };
+    assert_eq!(simplified("x*1 + 0"), "x");
+    assert_eq!(simplified("0*sin(x) + 1*x^1"), "x");
+    assert_eq!(simplified("x*2*3"), "6*x");
+    assert_eq!(simplified("x + -y"), "x - y");
+    assert_eq!(simplified("x - -2"), "x + 2");
+    assert_eq!(simplified("-(-x)"), "x");
+    assert_eq!(simplified("(-x)*y"), "-x*y");
+    assert_eq!(simplified("x*(-y)"), "-x*y");
+    assert_eq!(simplified("a*(2*x)"), "2*a*x");
+    assert_eq!(simplified("2^3 + 1/3"), "8 + 1/3");
+    assert_eq!(simplified("sin(x)/sin(x)"), "1");
+    assert_eq!(simplified("1/x*x + y*(2/y)"), "3");
+    assert_eq!(simplified("a + -2*x - -x*y + -x/y"), "a - 2*x + x*y - x/y");
+}
+
+/// Compares derivatives of all functions with central differences
+#[test]
+fn all_functions() {
+    let cases = [
+        ("abs(x - 1)", 0.3),
+        ("acos(x)", 0.3),
+        ("acosh(x)", 1.7),
+        ("asin(x)", 0.3),
+        ("asinh(x)", 0.3),
+        ("atan(x)", 0.3),
+        ("atan2(x, 2*x + 1)", 0.3),
+        ("atan2(1, x)", 0.3),
+        ("atanh(x)", 0.3),
+        ("cbrt(x)", 0.3),
+        ("ceil(x)", 0.3),
+        ("clamp(x^2, 0.1, 1)", 0.3),
+        ("clamp(2, x, x + 1)", 0.3),
+        ("clamp(x, 0, 0.1)", 0.3),
+        ("cos(x)", 0.3),
+        ("cosh(x)", 0.3),
+        ("exp(x)", 0.3),
+        ("exp2(x)", 0.3),
+        ("floor(x)", 0.3),
+        ("fract(3*x)", 0.3),
+        ("hypot(x, x^2)", 0.3),
+        ("ln(x)", 0.3),
+        ("log2(x)", 0.3),
+        ("log10(x)", 0.3),
+        ("max(x, 1 - x)", 0.3),
+        ("min(x, 1 - x)", 0.3),
+        ("mul_add(x, x^2, sin(x))", 0.3),
+        ("max(x, 1 - x, x^2)", 0.3),
+        ("min(x, 1 - x, x^2)", 0.3),
+        ("max(x^3)", 0.3),
+        ("hypot(x, 1, x^2)", 0.3),
+        ("sum(x, x^2, sin(x))", 0.3),
+        ("mean(x, x^2)", 0.3),
+        ("median(x, 2, 3)", 0.3),
+        ("median(x, 2*x^2, 1)", 0.6),
+        ("median(x, 0.1, x^2, sin(x))", 0.3),
+        ("stdev(x, x^2, 1)", 0.3),
+        ("pow(x, 3)", 0.3),
+        ("pow(2, x)", 0.3),
+        ("pow(x, x)", 0.3),
+        ("x^x", 0.3),
+        ("recip(x)", 0.3),
+        ("round(x)", 0.3),
+        ("sign(x)", 0.3),
+        ("sin(x)", 0.3),
+        ("sinh(x)", 0.3),
+        ("sqrt(x)", 0.3),
+        ("tan(x)", 0.3),
+        ("tanh(x)", 0.3),
+        ("toDeg(x)", 0.3),
+        ("toRad(x)", 0.3),
+        ("trunc(x)", 0.3),
+        ("x/(1 + x^2)", 0.3),
+        ("series(k, 1, 4, x^k/k)", 0.3),
+        ("series(x, 1, 3, x)*x", 0.3),
+        ("prod(k, 1, 3, k)*x", 0.3),
+        ("int(t, 0, x, exp(-t^2))", 0.3),
+        ("int(t, x, 2*x, t*x)", 0.3),
+        ("int(x, 0, 1, x^2)*x", 0.3),
+    ];
+    let env = Env::new();
+    for (text, x) in cases {
+        let expr = Expression::parse(text).unwrap();
+        let f = expr.closure("x", &env).unwrap();
+        let derivative = expr.derivative("x").unwrap();
+        let df = derivative.closure("x", &env).unwrap();
+        let h = 0.000_001;
+        let expected = (f(x + h) - f(x - h)) / (2.0 * h);
+        assert!(
+            f64::abs(df(x) - expected) < 0.000_01,
+            "{}: {} = {}, expected {}",
+            text,
+            derivative,
+            df(x),
+            expected
+        );
+    }
+}
+
+#[test]
+fn bound_operators() {
+    assert_eq!(
+        derivative("series(k, 1, n, x^k)"),
+        "series(k, 1, n, k*x^(k - 1))"
+    );
+    assert_eq!(derivative("int(t, 0, x, exp(-t^2))"), "exp(-x^2)");
+    assert_eq!(derivative("series(x, 1, n, x) + int(t, 0, 1, y)"), "0");
+
+    let error = Expression::parse("prod(k, 1, 3, x + k)")
+        .unwrap()
+        .derivative("x")
+        .unwrap_err();
+    assert_eq!(error.code(), "E401");
+}
+
+#[test]
+fn spans_point_to_source() {
+    let derivative = Expression::parse("x * y").unwrap().derivative("x").unwrap();
+    assert_eq!(derivative.to_string(), "y");
+    let error = derivative.eval(&Env::new()).unwrap_err();
+    assert_eq!(SolverError::span(&error), Span::new(4, 1));
+}
@@ -0,0 +1,353 @@
+use std::f64::consts::{FRAC_PI_2, LN_10, LN_2, PI};
+use std::fmt::{self, Display};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Complex number `re + im*i`. Operations on real numbers give the same
+/// results as on `f64`.
+///
+/// Multivalued functions take their principal branches: the argument is in
+/// `(-pi, pi]`, so the imaginary part of `ln` is too, and `sqrt` has
+/// non-negative real part. Real numbers lie on the upper side of the branch
+/// cuts whatever the sign of a zero imaginary part, as C99 `csqrt`, `casin`
+/// and the others give for `+0`: `sqrt(-4) = 2i`, `ln(-1) = pi*i`,
+/// `asin(2) = pi/2 + 1.3169578969248166i`.
+///
+/// Multiplying or dividing by a real number scales the components, exact
+/// zeros stay zero, so `i*inf` is `infi` rather than `NaN + infi`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+/// Imaginary unit
+pub const I: Complex = Complex::new(0.0, 1.0);
+
+impl Complex {
+    pub const fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// Number with modulus `r` and argument `theta` in radians
+    pub fn from_polar(r: f64, theta: f64) -> Self {
+        Self::new(r * theta.cos(), r * theta.sin())
+    }
+
+    /// Modulus and argument in `(-pi, pi]`
+    pub fn to_polar(self) -> (f64, f64) {
+        (self.abs(), self.arg())
+    }
+
+    /// Displays the number in polar form `r∠theta`, the angle in radians
+    pub fn polar(self) -> Polar {
+        Polar(self)
+    }
+
+    pub fn is_real(self) -> bool {
+        self.im == 0.0
+    }
+
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(self) -> f64 {
+        match self.is_real() {
+            true => f64::atan2(0.0, self.re),
+            false => self.im.atan2(self.re),
+        }
+    }
+
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    pub fn recip(self) -> Self {
+        Self::from(1.0) / self
+    }
+
+    pub fn exp(self) -> Self {
+        Self::from_polar(self.re.exp(), self.im)
+    }
+
+    /// Natural logarithm, the imaginary part is in `(-pi, pi]`
+    pub fn ln(self) -> Self {
+        Self::new(self.abs().ln(), self.arg())
+    }
+
+    /// Square root with non-negative real part
+    pub fn sqrt(self) -> Self {
+        if self.is_real() && self.re >= 0.0 {
+            return Self::from(self.re.sqrt());
+        }
+        let r = self.abs();
+        let re = (0.5 * (r + self.re)).sqrt();
+        let im = (0.5 * (r - self.re)).sqrt();
+        match self.is_real() {
+            true => Self::new(re, im),
+            false => Self::new(re, im.copysign(self.im)),
+        }
+    }
+
+    /// `self^exponent` as `exp(exponent*ln(self))`, integer powers are
+    /// multiplied out
+    pub fn pow(self, exponent: Self) -> Self {
+        let n = exponent.re;
+        if exponent.is_real() && n.fract() == 0.0 && n.abs() <= 64.0 {
+            let power = self.powi(n.abs() as u32);
+            return if n < 0.0 { power.recip() } else { power };
+        }
+        if self == Self::default() {
+            return match exponent.re > 0.0 {
+                true => Self::default(),
+                false => Self::from(f64::NAN),
+            };
+        }
+        (exponent * self.ln()).exp()
+    }
+
+    fn powi(self, mut n: u32) -> Self {
+        let (mut base, mut power) = (self, Self::from(1.0));
+        while n > 0 {
+            if n % 2 == 1 {
+                power = power * base;
+            }
+            base = base * base;
+            n /= 2;
+        }
+        power
+    }
+
+    pub fn sin(self) -> Self {
+        let (re, im) = (self.re, self.im);
+        Self::new(re.sin() * im.cosh(), re.cos() * im.sinh())
+    }
+
+    pub fn cos(self) -> Self {
+        let (re, im) = (self.re, self.im);
+        Self::new(re.cos() * im.cosh(), -re.sin() * im.sinh())
+    }
+
+    pub fn tan(self) -> Self {
+        self.sin() / self.cos()
+    }
+
+    pub fn sinh(self) -> Self {
+        let (re, im) = (self.re, self.im);
+        Self::new(re.sinh() * im.cos(), re.cosh() * im.sin())
+    }
+
+    pub fn cosh(self) -> Self {
+        let (re, im) = (self.re, self.im);
+        Self::new(re.cosh() * im.cos(), re.sinh() * im.sin())
+    }
+
+    pub fn tanh(self) -> Self {
+        self.sinh() / self.cosh()
+    }
+
+    /// `-i*ln(i*z + sqrt(1 - z^2))`, the cuts are the real axis below `-1`
+    /// and above `1`
+    pub fn asin(self) -> Self {
+        if self.is_real() && self.re.abs() > 1.0 {
+            return Self::new(FRAC_PI_2.copysign(self.re), self.re.abs().acosh());
+        }
+        let one = Self::from(1.0);
+        -I * (I * self + (one - self * self).sqrt()).ln()
+    }
+
+    /// `pi/2 - asin(z)`, with the cuts of `asin`
+    pub fn acos(self) -> Self {
+        Self::from(FRAC_PI_2) - self.asin()
+    }
+
+    /// `i/2*(ln(1 - i*z) - ln(1 + i*z))`, the cuts are the imaginary axis
+    /// below `-i` and above `i`
+    pub fn atan(self) -> Self {
+        let one = Self::from(1.0);
+        I * 0.5 * ((one - I * self).ln() - (one + I * self).ln())
+    }
+
+    /// `ln(z + sqrt(z^2 + 1))`, the cuts are the imaginary axis below `-i`
+    /// and above `i`
+    pub fn asinh(self) -> Self {
+        (self + (self * self + Self::from(1.0)).sqrt()).ln()
+    }
+
+    /// `ln(z + sqrt(z + 1)*sqrt(z - 1))`, the cut is the real axis below `1`
+    pub fn acosh(self) -> Self {
+        let one = Self::from(1.0);
+        (self + (self + one).sqrt() * (self - one).sqrt()).ln()
+    }
+
+    /// `(ln(1 + z) - ln(1 - z))/2`, the cuts are the real axis below `-1`
+    /// and above `1`
+    pub fn atanh(self) -> Self {
+        if self.is_real() && self.re.abs() > 1.0 {
+            let x = self.re;
+            return Self::new(0.5 * ((x + 1.0) / (x - 1.0)).ln(), FRAC_PI_2);
+        }
+        let one = Self::from(1.0);
+        ((one + self).ln() - (one - self).ln()) * 0.5
+    }
+}
+
+/// Built-in function `name` applied to `args`, `real` is its version on
+/// real numbers. It is used for real arguments unless it gives NaN, which
+/// the complex version may not. `None` for functions only defined on real
+/// numbers given complex arguments
+pub(crate) fn call(name: &str, args: &[Complex], real: impl Fn(&[f64]) -> f64) -> Option<Complex> {
+    let real = args.iter().all(|a| a.is_real()).then(|| {
+        let reals: Vec<f64> = args.iter().map(|a| a.re).collect();
+        Complex::from(real(&reals))
+    });
+    if let Some(value) = real.filter(|value| !value.re.is_nan()) {
+        return Some(value);
+    }
+    let z = args[0];
+    let total = || args.iter().fold(Complex::default(), |acc, &a| acc + a);
+    let value = match name {
+        "abs" => Complex::from(z.abs()),
+        "acos" => z.acos(),
+        "acosh" => z.acosh(),
+        "asin" => z.asin(),
+        "asinh" => z.asinh(),
+        "atan" => z.atan(),
+        "atanh" => z.atanh(),
+        "cbrt" => z.pow(Complex::from(1.0 / 3.0)),
+        "cos" => z.cos(),
+        "cosh" => z.cosh(),
+        "exp" => z.exp(),
+        "exp2" => (z * LN_2).exp(),
+        "hypot" => Complex::from(args.iter().fold(0.0, |h: f64, a| h.hypot(a.abs()))),
+        "ln" => z.ln(),
+        "log2" => z.ln() / Complex::from(LN_2),
+        "log10" => z.ln() / Complex::from(LN_10),
+        "mean" => total() / Complex::from(args.len() as f64),
+        "mul_add" => z * args[1] + args[2],
+        "pow" => z.pow(args[1]),
+        "recip" => z.recip(),
+        "sign" if z == Complex::default() => z,
+        "sign" => z / Complex::from(z.abs()),
+        "sin" => z.sin(),
+        "sinh" => z.sinh(),
+        "sqrt" => z.sqrt(),
+        "sum" => total(),
+        "tan" => z.tan(),
+        "tanh" => z.tanh(),
+        "toDeg" => z * (180.0 / PI),
+        "toRad" => z * (PI / 180.0),
+        _ => return real,
+    };
+    Some(value)
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Self::new(re, 0.0)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Complex) -> Complex {
+        // real factors only scale, so infinities do not turn into NaN
+        match (self.is_real(), rhs.is_real()) {
+            (true, _) => rhs * self.re,
+            (_, true) => self * rhs.re,
+            _ => Complex::new(
+                self.re * rhs.re - self.im * rhs.im,
+                self.re * rhs.im + self.im * rhs.re,
+            ),
+        }
+    }
+}
+
+impl Mul<f64> for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: f64) -> Complex {
+        match self.is_real() {
+            true => Complex::from(self.re * rhs),
+            false => Complex::new(scale(self.re, rhs, f64::mul), self.im * rhs),
+        }
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, rhs: Complex) -> Complex {
+        if rhs.is_real() {
+            return match self.is_real() {
+                true => Complex::from(self.re / rhs.re),
+                false => Complex::new(scale(self.re, rhs.re, f64::div), self.im / rhs.re),
+            };
+        }
+        // Smith's algorithm avoids overflow of the squared modulus
+        let (a, b, c, d) = (self.re, self.im, rhs.re, rhs.im);
+        if c.abs() >= d.abs() {
+            let (r, den) = (d / c, c + d * (d / c));
+            Complex::new((a + b * r) / den, (b - a * r) / den)
+        } else {
+            let (r, den) = (c / d, c * (c / d) + d);
+            Complex::new((a * r + b) / den, (b * r - a) / den)
+        }
+    }
+}
+
+/// Component `x` scaled by a real number with `op`, an exact zero stays zero
+fn scale(x: f64, by: f64, op: fn(f64, f64) -> f64) -> f64 {
+    match x == 0.0 {
+        true => x,
+        false => op(x, by),
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+
+    fn neg(self) -> Complex {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+/// Rectangular form `a + bi`, real numbers are printed as such
+impl Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.re, self.im) {
+            (re, 0.0) => write!(f, "{}", re),
+            (0.0, im) => write!(f, "{}i", im),
+            (re, im) if im.is_sign_negative() => write!(f, "{} - {}i", re, -im),
+            (re, im) => write!(f, "{} + {}i", re, im),
+        }
+    }
+}
+
+/// Polar form `r∠theta` of a complex number, see [`Complex::polar`]
+#[derive(Clone, Copy, Debug)]
+pub struct Polar(Complex);
+
+impl Display for Polar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (r, theta) = self.0.to_polar();
+        write!(f, "{}∠{}", r, theta)
+    }
+}
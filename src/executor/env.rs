@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+/// Values of the variables an expression is evaluated with, real numbers by
+/// default and [`Complex`](super::Complex) ones in complex mode
+#[derive(Clone, Debug, PartialEq)]
+pub struct Env<T = f64> {
+    vars: HashMap<String, T>,
+}
+
+impl<T: Copy> Env<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builder-style version of [`Env::set`]
+    pub fn with(mut self, name: impl Into<String>, value: T) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, value: T) {
+        self.vars.insert(name.into(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<T> {
+        self.vars.get(name).copied()
+    }
+}
+
+impl<T> Default for Env<T> {
+    fn default() -> Self {
+        Self {
+            vars: HashMap::new(),
+        }
+    }
+}
+
+impl<S: Into<String>, T> FromIterator<(S, T)> for Env<T> {
+    fn from_iter<I: IntoIterator<Item = (S, T)>>(iter: I) -> Self {
+        Self {
+            vars: iter
+                .into_iter()
+                .map(|(name, value)| (name.into(), value))
+                .collect(),
+        }
+    }
+}
@@ -1,51 +1,46 @@
-use crate::colors::{bold_red, bold_yellow};
-use crate::solver_error::SolverError;
+use crate::solver_error::{Diagnostic, ErrorType};
 
-#[derive(Clone, Debug)]
-pub struct ExecutionError<'src> {
-    text: &'src str,
-    pos: usize,
-    error_type: ExecutionErrorType,
-}
+pub type ExecutionError = Diagnostic<ExecutionErrorType>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ExecutionErrorType {
-    MissingArgumentValue,
-    UnmatchedOperator,
-    MissigOperator,
-    WrongArgs,
+    UnknownIdent,
+    InvalidBounds,
+    NoConvergence,
+    /// Complex argument of a function only defined on real numbers
+    NotReal,
 }
 
-impl<'src> ExecutionError<'src> {
-    pub fn new(pos: usize, text: &'src str, error_type: ExecutionErrorType) -> Self {
-        Self {
-            pos,
-            text,
-            error_type,
+impl ErrorType for ExecutionErrorType {
+    fn code(&self) -> &'static str {
+        use ExecutionErrorType::*;
+        match self {
+            UnknownIdent => "E301",
+            InvalidBounds => "E303",
+            NoConvergence => "E304",
+            NotReal => "E305",
         }
     }
-}
 
-impl SolverError for ExecutionError<'_> {
-    fn display_solver_error(&self, source: &str) -> String {
+    fn message(&self) -> &'static str {
         use ExecutionErrorType::*;
+        match self {
+            UnknownIdent => "unknown identifier",
+            InvalidBounds => "bounds are not finite integers for",
+            NoConvergence => "integral did not converge for",
+            NotReal => "complex arguments are not supported by",
+        }
+    }
 
-        let msg = match self.error_type {
-            MissingArgumentValue => "argument value is required",
-            UnmatchedOperator => "missing operand for",
-            MissigOperator => "missing operator",
-            WrongArgs => "wrong arguments for",
-        };
-
-        let brief = format!("{} `{}` at {}", msg, self.text, self.pos);
-        let cursor = " ".repeat(self.pos) + &"^".repeat(self.text.len());
-
-        format!(
-            "{}: {}\n{}\n{}",
-            bold_red("error"),
-            brief,
-            source,
-            bold_yellow(cursor),
-        )
+    fn help(&self) -> Option<&'static str> {
+        use ExecutionErrorType::*;
+        match self {
+            UnknownIdent => Some("the variable is not bound to a value"),
+            InvalidBounds => {
+                Some("`series` and `prod` step the variable by one from the lower bound")
+            }
+            NotReal => Some("the function is only defined on real numbers"),
+            _ => None,
+        }
     }
 }
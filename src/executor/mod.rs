@@ -1,84 +1,387 @@
-use crate::tokens::{Token, TokenType};
+use std::cell::RefCell;
+use std::slice;
+
+use crate::ast::{substitute, BinaryOp, BoundOp, Node, NodeKind, UnaryOp};
+use crate::diff::errors::{DiffError, DiffErrorType};
+use crate::optimizer::is_builtin;
+use crate::solver_error::Error;
+use crate::solvers::{integral, IntegralMethod};
 use errors::{ExecutionError, ExecutionErrorType};
 
-mod errors;
+pub use complex::{Complex, Polar, I};
+pub use env::Env;
+pub use taylor::Taylor;
+
+mod complex;
+mod env;
+pub mod errors;
+mod taylor;
 
 #[cfg(test)]
 mod tests;
 
-pub fn eval<'src>(
-    postfix_list: &Vec<Token<'src>>,
-    x: Option<f64>,
-) -> Result<f64, ExecutionError<'src>> {
-    use ExecutionErrorType::*;
-    use TokenType::*;
-
-    let mut stack = Vec::new();
-    let mut tokens_it = postfix_list.iter();
-    let mut args_stack = Vec::new();
-
-    while let Some(token) = tokens_it.next() {
-        match token.token_type {
-            Number(n) => stack.push(n),
-            Var => match x {
-                Some(value) => stack.push(value),
-                None => return Err(get_execution_err(*token, MissingArgumentValue)),
-            },
-            Const(n) => stack.push(n),
-            UM => match stack.last_mut() {
-                Some(val) => *val *= -1.0,
-                _ => return Err(get_execution_err(*token, UnmatchedOperator)),
-            },
-            Add => match (stack.pop(), stack.last_mut()) {
-                (Some(val1), Some(val2)) => *val2 += val1,
-                _ => return Err(get_execution_err(*token, UnmatchedOperator)),
-            },
-            Sub => match (stack.pop(), stack.last_mut()) {
-                (Some(val1), Some(val2)) => *val2 -= val1,
-                _ => return Err(get_execution_err(*token, UnmatchedOperator)),
-            },
-            Mul => match (stack.pop(), stack.last_mut()) {
-                (Some(val1), Some(val2)) => *val2 *= val1,
-                _ => return Err(get_execution_err(*token, UnmatchedOperator)),
-            },
-            Div => match (stack.pop(), stack.last_mut()) {
-                (Some(val1), Some(val2)) => *val2 /= val1,
-                _ => return Err(get_execution_err(*token, UnmatchedOperator)),
-            },
-            Pow => match (stack.pop(), stack.last_mut()) {
-                (Some(val1), Some(val2)) => *val2 = val2.powf(val1),
-                _ => return Err(get_execution_err(*token, UnmatchedOperator)),
-            },
-            Comma => match stack.pop() {
-                Some(val) => args_stack.push(val),
-                _ => return Err(get_execution_err(*token, UnmatchedOperator)),
-            },
-            Func { args, func } => {
-                let first_arg = match stack.pop() {
-                    Some(val) => val,
-                    _ => return Err(get_execution_err(*token, WrongArgs)),
-                };
-                if args_stack.len() + 1 != args {
-                    return Err(get_execution_err(*token, WrongArgs));
+/// Accuracy of `int` relative to the magnitude of the integral
+const INT_EPS: f64 = 0.000_000_000_1;
+
+/// Variable lookup inside the body of a bound operator. It nests the lookup of
+/// the enclosing expression, so it is not generic
+type Lookup<'a, T = f64> = dyn Fn(&str) -> Option<T> + 'a;
+
+/// Evaluates expression tree resolving variables with `lookup`
+pub fn eval<F>(node: &Node, lookup: &F) -> Result<f64, ExecutionError>
+where
+    F: Fn(&str) -> Option<f64> + ?Sized,
+{
+    use NodeKind::*;
+
+    Ok(match &node.kind {
+        Number(n) => *n,
+        Const { value, .. } => *value,
+        Var(name) => match lookup(name) {
+            Some(value) => value,
+            None => return Err(unknown_ident(node.span.pos, name)),
+        },
+        Unary {
+            op: UnaryOp::Neg,
+            operand,
+        } => -eval(operand, lookup)?,
+        Binary { op, lhs, rhs } => {
+            let lhs = eval(lhs, lookup)?;
+            let rhs = eval(rhs, lookup)?;
+            match op {
+                BinaryOp::Add => lhs + rhs,
+                BinaryOp::Sub => lhs - rhs,
+                BinaryOp::Mul => lhs * rhs,
+                BinaryOp::Div => lhs / rhs,
+                BinaryOp::Pow => lhs.powf(rhs),
+            }
+        }
+        Call { func, args, .. } => {
+            // most functions take few arguments, those are passed on the stack
+            let mut buffer = [0.0; 4];
+            if args.len() > buffer.len() {
+                let args = args
+                    .iter()
+                    .map(|arg| eval(arg, lookup))
+                    .collect::<Result<Vec<_>, _>>()?;
+                return Ok(func.call(&args));
+            }
+            for (value, arg) in buffer.iter_mut().zip(args) {
+                *value = eval(arg, lookup)?;
+            }
+            func.call(&buffer[..args.len()])
+        }
+        Bound {
+            op,
+            var,
+            from,
+            to,
+            body,
+        } => {
+            let (from, to) = (eval(from, lookup)?, eval(to, lookup)?);
+            let at = |t: f64| eval(body, &bind(var, t, lookup) as &Lookup);
+            match op {
+                BoundOp::Series => steps(node.span.pos, *op, from, to)?
+                    .try_fold(0.0, |acc, k| Ok::<_, ExecutionError>(acc + at(k)?))?,
+                BoundOp::Prod => steps(node.span.pos, *op, from, to)?
+                    .try_fold(1.0, |acc, k| Ok::<_, ExecutionError>(acc * at(k)?))?,
+                BoundOp::Int => integrate(node.span.pos, from, to, at)?,
+            }
+        }
+        Let { var, value, body } => {
+            let value = eval(value, lookup)?;
+            eval(body, &bind(var, value, lookup) as &Lookup)?
+        }
+    })
+}
+
+/// Evaluates expression tree as a Taylor series of order `order` in variable
+/// `var` at `x`, giving the derivatives up to `order` along with the value.
+/// Other variables are resolved with `lookup`. Functions without a known
+/// derivative fail the same as in [`diff`](crate::diff::diff)
+pub fn eval_taylor<F>(
+    node: &Node,
+    var: &str,
+    x: f64,
+    order: usize,
+    lookup: &F,
+) -> Result<Taylor, Error>
+where
+    F: Fn(&str) -> Option<f64> + ?Sized,
+{
+    use NodeKind::*;
+    let eval = |node: &Node| eval_taylor(node, var, x, order, lookup);
+
+    Ok(match &node.kind {
+        Number(n) => Taylor::constant(*n, order),
+        Const { value, .. } => Taylor::constant(*value, order),
+        Var(name) if name == var => Taylor::variable(x, order),
+        Var(name) => match lookup(name) {
+            Some(value) => Taylor::constant(value, order),
+            None => return Err(unknown_ident(node.span.pos, name).into()),
+        },
+        Unary {
+            op: UnaryOp::Neg,
+            operand,
+        } => -eval(operand)?,
+        Binary { op, lhs, rhs } => {
+            let lhs = eval(lhs)?;
+            let rhs = eval(rhs)?;
+            match op {
+                BinaryOp::Add => lhs + rhs,
+                BinaryOp::Sub => lhs - rhs,
+                BinaryOp::Mul => lhs * rhs,
+                BinaryOp::Div => lhs / rhs,
+                BinaryOp::Pow => lhs.pow(&rhs),
+            }
+        }
+        Call { name, args, .. } => {
+            let args = args.iter().map(eval).collect::<Result<Vec<_>, _>>()?;
+            taylor::call(name, &args).ok_or_else(|| {
+                DiffError::new(node.span.pos, name, DiffErrorType::NotDifferentiable)
+            })?
+        }
+        Bound {
+            op,
+            var: bound,
+            from,
+            to,
+            body,
+        } => {
+            let (a, b) = (eval(from)?, eval(to)?);
+            // the variable of the series is hidden by the bound one
+            let at = |t: f64| match bound == var {
+                true => Ok(Taylor::constant(
+                    self::eval(body, &bind(bound, t, lookup) as &Lookup)?,
+                    order,
+                )),
+                false => eval_taylor(body, var, x, order, &bind(bound, t, lookup) as &Lookup),
+            };
+            let (a_value, b_value) = (a.value(), b.value());
+            match op {
+                // integer bounds are piecewise constant
+                BoundOp::Series => steps(node.span.pos, *op, a_value, b_value)?
+                    .try_fold(Taylor::constant(0.0, order), |acc, k| {
+                        Ok::<_, Error>(acc + at(k)?)
+                    })?,
+                BoundOp::Prod => steps(node.span.pos, *op, a_value, b_value)?
+                    .try_fold(Taylor::constant(1.0, order), |acc, k| {
+                        Ok::<_, Error>(acc * at(k)?)
+                    })?,
+                BoundOp::Int if bound != var && body.depends_on(var) => {
+                    if from.depends_on(var) || to.depends_on(var) {
+                        return Err(DiffError::new(
+                            node.span.pos,
+                            BoundOp::Int.name(),
+                            DiffErrorType::NotDifferentiable,
+                        )
+                        .with_help("either the bounds or the integrand may depend on the variable")
+                        .into());
+                    }
+                    // the series of the integral is the integral of the series
+                    let coefficients = (0..=order)
+                        .map(|k| {
+                            integrate(node.span.pos, a_value, b_value, |t| {
+                                Ok::<_, Error>(at(t)?.coefficients()[k])
+                            })
+                        })
+                        .collect::<Result<_, _>>()?;
+                    Taylor::with_coefficients(coefficients)
+                }
+                BoundOp::Int => {
+                    let value = integrate(node.span.pos, a_value, b_value, |t| {
+                        self::eval(body, &bind(bound, t, lookup) as &Lookup)
+                    })?;
+                    let mut series = Taylor::constant(value, order);
+                    if to.depends_on(var) {
+                        let integrand = eval_taylor(body, bound, b_value, order, lookup)?;
+                        series = series + b.integral_from_value(&integrand);
+                    }
+                    if from.depends_on(var) {
+                        let integrand = eval_taylor(body, bound, a_value, order, lookup)?;
+                        series = series - a.integral_from_value(&integrand);
+                    }
+                    series
                 }
-                let mut arguments = vec![first_arg];
-                args_stack.reverse();
-                arguments.append(&mut args_stack.clone());
-                args_stack.clear();
+            }
+        }
+        // the series of the shared value can not be looked up, it is written
+        // out at every use instead
+        Let {
+            var: bound,
+            value,
+            body,
+        } => eval(&substitute(
+            body,
+            slice::from_ref(bound),
+            slice::from_ref(value),
+            node.span,
+        ))?,
+    })
+}
 
-                stack.push(func(arguments));
+/// Evaluates expression tree on complex numbers resolving variables with
+/// `lookup`. Functions only defined on real numbers, and bounds of bound
+/// operators, fail unless their values are real
+pub fn eval_complex<F>(node: &Node, lookup: &F) -> Result<Complex, ExecutionError>
+where
+    F: Fn(&str) -> Option<Complex> + ?Sized,
+{
+    use NodeKind::*;
+    let not_real =
+        |name: &str| ExecutionError::new(node.span.pos, name, ExecutionErrorType::NotReal);
+
+    Ok(match &node.kind {
+        Number(n) => Complex::from(*n),
+        Const { value, .. } => Complex::from(*value),
+        Var(name) => match lookup(name) {
+            Some(value) => value,
+            None => return Err(unknown_ident(node.span.pos, name)),
+        },
+        Unary {
+            op: UnaryOp::Neg,
+            operand,
+        } => -eval_complex(operand, lookup)?,
+        Binary { op, lhs, rhs } => {
+            let lhs = eval_complex(lhs, lookup)?;
+            let rhs = eval_complex(rhs, lookup)?;
+            match op {
+                BinaryOp::Add => lhs + rhs,
+                BinaryOp::Sub => lhs - rhs,
+                BinaryOp::Mul => lhs * rhs,
+                BinaryOp::Div => lhs / rhs,
+                BinaryOp::Pow => match lhs.re.powf(rhs.re) {
+                    value if lhs.is_real() && rhs.is_real() && !value.is_nan() => {
+                        Complex::from(value)
+                    }
+                    _ => lhs.pow(rhs),
+                },
+            }
+        }
+        Call { name, func, args } => {
+            let args = args
+                .iter()
+                .map(|arg| eval_complex(arg, lookup))
+                .collect::<Result<Vec<_>, _>>()?;
+            let value = match is_builtin(name, func) {
+                true => complex::call(name, &args, |reals| func.call(reals)),
+                false => args.iter().all(|a| a.is_real()).then(|| {
+                    let reals: Vec<f64> = args.iter().map(|a| a.re).collect();
+                    Complex::from(func.call(&reals))
+                }),
+            };
+            value.ok_or_else(|| not_real(name))?
+        }
+        Bound {
+            op,
+            var,
+            from,
+            to,
+            body,
+        } => {
+            let (from, to) = (eval_complex(from, lookup)?, eval_complex(to, lookup)?);
+            if !from.is_real() || !to.is_real() {
+                return Err(not_real(op.name()).with_help("the bounds must be real"));
             }
-            LParen { .. } | RParen { .. } => (),
+            let at =
+                |t: f64| eval_complex(body, &bind(var, Complex::from(t), lookup) as &Lookup<_>);
+            let (from, to) = (from.re, to.re);
+            match op {
+                BoundOp::Series => steps(node.span.pos, *op, from, to)?
+                    .try_fold(Complex::default(), |acc, k| {
+                        Ok::<_, ExecutionError>(acc + at(k)?)
+                    })?,
+                BoundOp::Prod => steps(node.span.pos, *op, from, to)?
+                    .try_fold(Complex::from(1.0), |acc, k| {
+                        Ok::<_, ExecutionError>(acc * at(k)?)
+                    })?,
+                // the real and imaginary parts are integrated one after the other
+                BoundOp::Int => Complex::new(
+                    integrate(node.span.pos, from, to, |t| Ok(at(t)?.re))?,
+                    integrate(node.span.pos, from, to, |t| Ok(at(t)?.im))?,
+                ),
+            }
+        }
+        Let { var, value, body } => {
+            let value = eval_complex(value, lookup)?;
+            eval_complex(body, &bind(var, value, lookup) as &Lookup<_>)?
         }
+    })
+}
+
+/// Lookup with variable `var` bound to `value`
+fn bind<'a, T, F>(var: &'a str, value: T, lookup: &'a F) -> impl Fn(&str) -> Option<T> + 'a
+where
+    T: Copy + 'a,
+    F: Fn(&str) -> Option<T> + ?Sized,
+{
+    move |name| match name == var {
+        true => Some(value),
+        false => lookup(name),
     }
+}
 
-    if stack.len() > 1 {
-        return Err(ExecutionError::new(0, "", MissigOperator));
+/// Values the variable of `series` and `prod` takes from `from` to `to`
+fn steps(
+    pos: usize,
+    op: BoundOp,
+    from: f64,
+    to: f64,
+) -> Result<impl Iterator<Item = f64>, ExecutionError> {
+    check_steps(pos, op, from, to)?;
+    Ok((0..)
+        .map(move |i| from + i as f64)
+        .take_while(move |&k| k <= to))
+}
+
+/// Checks that the bounds of `series` or `prod` at `pos` are integers
+pub(crate) fn check_steps(
+    pos: usize,
+    op: BoundOp,
+    from: f64,
+    to: f64,
+) -> Result<(), ExecutionError> {
+    let integer = |n: f64| n.is_finite() && n.fract() == 0.0;
+    match integer(from) && integer(to) {
+        true => Ok(()),
+        false => Err(ExecutionError::new(
+            pos,
+            op.name(),
+            ExecutionErrorType::InvalidBounds,
+        )),
     }
+}
 
-    Ok(stack[0])
+/// Integral of `f` from `from` to `to` for `int` at `pos`, the first error of
+/// `f` is returned
+pub(crate) fn integrate<F, E>(pos: usize, from: f64, to: f64, f: F) -> Result<f64, E>
+where
+    F: Fn(f64) -> Result<f64, E>,
+    E: From<ExecutionError>,
+{
+    let error = RefCell::new(None);
+    let f = |t| {
+        f(t).unwrap_or_else(|e| {
+            error.borrow_mut().get_or_insert(e);
+            f64::NAN
+        })
+    };
+    let method = IntegralMethod::default();
+    // the tolerance is scaled with a first estimate of the integral
+    let estimate = integral(f, from, to, f64::INFINITY, None, method).value;
+    let eps = INT_EPS * estimate.abs().max(1.0);
+    let report = integral(f, from, to, eps, None, method);
+    if let Some(error) = error.into_inner() {
+        return Err(error);
+    }
+    report.ok().ok_or_else(|| {
+        ExecutionError::new(pos, BoundOp::Int.name(), ExecutionErrorType::NoConvergence)
+            .with_help(format!("integration stopped: {}", report.termination))
+            .into()
+    })
 }
 
-fn get_execution_err(token: Token, error_type: ExecutionErrorType) -> ExecutionError {
-    ExecutionError::new(token.pos, token.text, error_type)
+/// Error of variable `name` at `pos` without a value
+pub(crate) fn unknown_ident(pos: usize, name: &str) -> ExecutionError {
+    ExecutionError::new(pos, name, ExecutionErrorType::UnknownIdent)
 }
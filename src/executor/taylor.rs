@@ -0,0 +1,407 @@
+use std::f64::consts::{LN_10, LN_2};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Truncated Taylor series `c[0] + c[1]*t + ... + c[n]*t^n` of a value
+/// depending on a variable shifted by `t`. Order 1 is a dual number carrying
+/// the value and the first derivative
+#[derive(Clone, Debug, PartialEq)]
+pub struct Taylor {
+    coefficients: Vec<f64>,
+}
+
+impl Taylor {
+    pub fn constant(value: f64, order: usize) -> Self {
+        let mut coefficients = vec![0.0; order + 1];
+        coefficients[0] = value;
+        Self { coefficients }
+    }
+
+    /// The variable itself at `x`
+    pub fn variable(x: f64, order: usize) -> Self {
+        let mut taylor = Self::constant(x, order);
+        if order > 0 {
+            taylor.coefficients[1] = 1.0;
+        }
+        taylor
+    }
+
+    pub fn order(&self) -> usize {
+        self.coefficients.len() - 1
+    }
+
+    pub fn value(&self) -> f64 {
+        self.coefficients[0]
+    }
+
+    pub fn coefficients(&self) -> &[f64] {
+        &self.coefficients
+    }
+
+    /// `k`-th derivative, `k` must not exceed the order
+    pub fn derivative(&self, k: usize) -> f64 {
+        let factorial: f64 = (1..=k).map(|i| i as f64).product();
+        self.coefficients[k] * factorial
+    }
+
+    /// The value followed by all derivatives up to the order
+    pub fn derivatives(&self) -> Vec<f64> {
+        (0..=self.order()).map(|k| self.derivative(k)).collect()
+    }
+
+    pub(crate) fn with_coefficients(coefficients: Vec<f64>) -> Self {
+        Self { coefficients }
+    }
+
+    fn same_order(&self, value: f64) -> Self {
+        Self::constant(value, self.order())
+    }
+
+    /// Multiplies by a number
+    fn scale(&self, factor: f64) -> Self {
+        Self::with_coefficients(self.coefficients.iter().map(|c| c * factor).collect())
+    }
+
+    /// `f(self)` given the value of `f` and the series of its derivative `df`
+    /// at `self`. Follows from `(f∘u)' = f'(u)*u'` which only needs `df` to one
+    /// order less
+    fn compose(&self, value: f64, df: &Taylor) -> Self {
+        let (u, v) = (&self.coefficients, &df.coefficients);
+        let rest = (1..u.len()).map(|k| {
+            let sum: f64 = (1..=k).map(|j| j as f64 * u[j] * v[k - j]).sum();
+            sum / k as f64
+        });
+        Self::with_coefficients(std::iter::once(value).chain(rest).collect())
+    }
+
+    /// Integral of `f` from the value of `self` to `self`, given the series
+    /// `integrand` of `f` at that value. `f(self)` is expanded with Horner's
+    /// scheme in the shift of `self` from its value
+    pub(crate) fn integral_from_value(&self, integrand: &Taylor) -> Self {
+        let shift = self.clone() - self.same_order(self.value());
+        let f = integrand
+            .coefficients
+            .iter()
+            .rev()
+            .fold(self.same_order(0.0), |acc, &c| {
+                acc * shift.clone() + self.same_order(c)
+            });
+        self.compose(0.0, &f)
+    }
+
+    pub fn recip(&self) -> Self {
+        self.same_order(1.0) / self.clone()
+    }
+
+    pub fn exp(&self) -> Self {
+        let u = &self.coefficients;
+        let mut w = vec![0.0; u.len()];
+        w[0] = u[0].exp();
+        for k in 1..u.len() {
+            let sum: f64 = (1..=k).map(|j| j as f64 * u[j] * w[k - j]).sum();
+            w[k] = sum / k as f64;
+        }
+        Self::with_coefficients(w)
+    }
+
+    pub fn ln(&self) -> Self {
+        self.compose(self.value().ln(), &self.recip())
+    }
+
+    /// Sine and cosine computed together as each is the derivative of the
+    /// other, `hyperbolic` gives `sinh` and `cosh` instead
+    fn sin_cos(&self, hyperbolic: bool) -> (Self, Self) {
+        let u = &self.coefficients;
+        let (mut s, mut c) = (vec![0.0; u.len()], vec![0.0; u.len()]);
+        (s[0], c[0]) = match hyperbolic {
+            true => (u[0].sinh(), u[0].cosh()),
+            false => u[0].sin_cos(),
+        };
+        let sign = if hyperbolic { 1.0 } else { -1.0 };
+        for k in 1..u.len() {
+            let (mut s_sum, mut c_sum) = (0.0, 0.0);
+            for j in 1..=k {
+                s_sum += j as f64 * u[j] * c[k - j];
+                c_sum += j as f64 * u[j] * s[k - j];
+            }
+            s[k] = s_sum / k as f64;
+            c[k] = sign * c_sum / k as f64;
+        }
+        (Self::with_coefficients(s), Self::with_coefficients(c))
+    }
+
+    pub fn sin(&self) -> Self {
+        self.sin_cos(false).0
+    }
+
+    pub fn cos(&self) -> Self {
+        self.sin_cos(false).1
+    }
+
+    pub fn tan(&self) -> Self {
+        let (sin, cos) = self.sin_cos(false);
+        sin / cos
+    }
+
+    pub fn sinh(&self) -> Self {
+        self.sin_cos(true).0
+    }
+
+    pub fn cosh(&self) -> Self {
+        self.sin_cos(true).1
+    }
+
+    pub fn tanh(&self) -> Self {
+        let (sinh, cosh) = self.sin_cos(true);
+        sinh / cosh
+    }
+
+    pub fn sqrt(&self) -> Self {
+        let u = &self.coefficients;
+        let mut w = vec![0.0; u.len()];
+        w[0] = u[0].sqrt();
+        for k in 1..u.len() {
+            let sum: f64 = (1..k).map(|j| w[j] * w[k - j]).sum();
+            w[k] = (u[k] - sum) / (2.0 * w[0]);
+        }
+        Self::with_coefficients(w)
+    }
+
+    pub fn cbrt(&self) -> Self {
+        let sign = self.value().signum();
+        self.scale(sign).powf(1.0 / 3.0).scale(sign)
+    }
+
+    /// Power with a constant exponent. Small natural exponents are multiplied
+    /// out so that they work at zero
+    pub fn powf(&self, exponent: f64) -> Self {
+        if exponent.fract() == 0.0 && (0.0..=64.0).contains(&exponent) {
+            let mut result = self.same_order(1.0);
+            let mut base = self.clone();
+            let mut n = exponent as u32;
+            while n > 0 {
+                if n & 1 == 1 {
+                    result = result * base.clone();
+                }
+                base = base.clone() * base;
+                n >>= 1;
+            }
+            return result;
+        }
+        let u = &self.coefficients;
+        let mut w = vec![0.0; u.len()];
+        w[0] = u[0].powf(exponent);
+        for k in 1..u.len() {
+            let sum: f64 = (1..=k)
+                .map(|j| (exponent * j as f64 - (k - j) as f64) * u[j] * w[k - j])
+                .sum();
+            w[k] = sum / (k as f64 * u[0]);
+        }
+        Self::with_coefficients(w)
+    }
+
+    pub fn pow(&self, exponent: &Taylor) -> Self {
+        match exponent.coefficients[1..].iter().all(|&c| c == 0.0) {
+            true => self.powf(exponent.value()),
+            false => (exponent.clone() * self.ln()).exp(),
+        }
+    }
+
+    pub fn asin(&self) -> Self {
+        let df = (self.same_order(1.0) - self.clone() * self.clone())
+            .sqrt()
+            .recip();
+        self.compose(self.value().asin(), &df)
+    }
+
+    pub fn acos(&self) -> Self {
+        let df = -(self.same_order(1.0) - self.clone() * self.clone())
+            .sqrt()
+            .recip();
+        self.compose(self.value().acos(), &df)
+    }
+
+    pub fn atan(&self) -> Self {
+        let df = (self.same_order(1.0) + self.clone() * self.clone()).recip();
+        self.compose(self.value().atan(), &df)
+    }
+
+    pub fn asinh(&self) -> Self {
+        let df = (self.clone() * self.clone() + self.same_order(1.0))
+            .sqrt()
+            .recip();
+        self.compose(self.value().asinh(), &df)
+    }
+
+    pub fn acosh(&self) -> Self {
+        let df = (self.clone() * self.clone() - self.same_order(1.0))
+            .sqrt()
+            .recip();
+        self.compose(self.value().acosh(), &df)
+    }
+
+    pub fn atanh(&self) -> Self {
+        let df = (self.same_order(1.0) - self.clone() * self.clone()).recip();
+        self.compose(self.value().atanh(), &df)
+    }
+
+    /// Angle of the point `(x, self)`
+    pub fn atan2(&self, x: &Taylor) -> Self {
+        let y = self;
+        // (atan2(y, x))' = (x*y' - y*x') / (x^2 + y^2) also holds at `x = 0`
+        let df = (x.clone() * x.clone() + y.clone() * y.clone()).recip();
+        let numerator = x.clone() * y.derivative_series() - y.clone() * x.derivative_series();
+        let dw = numerator * df;
+        let rest = (1..dw.coefficients.len()).map(|k| dw.coefficients[k - 1] / k as f64);
+        let value = y.value().atan2(x.value());
+        Self::with_coefficients(std::iter::once(value).chain(rest).collect())
+    }
+
+    /// Series of the derivative with respect to `t`, the highest coefficient
+    /// is unknown and left zero
+    fn derivative_series(&self) -> Self {
+        let c = &self.coefficients;
+        let mut d = vec![0.0; c.len()];
+        for k in 0..c.len() - 1 {
+            d[k] = (k + 1) as f64 * c[k + 1];
+        }
+        Self::with_coefficients(d)
+    }
+
+    /// Constant with the value of a piecewise constant function
+    fn flat(&self, f: fn(f64) -> f64) -> Self {
+        self.same_order(f(self.value()))
+    }
+}
+
+/// Series of a built-in function applied to `args`, `None` for unknown names
+pub(crate) fn call(name: &str, args: &[Taylor]) -> Option<Taylor> {
+    let u = &args[0];
+    Some(match name {
+        "abs" => u.scale(u.value().signum()),
+        "acos" => u.acos(),
+        "acosh" => u.acosh(),
+        "asin" => u.asin(),
+        "asinh" => u.asinh(),
+        "atan" => u.atan(),
+        "atan2" => u.atan2(&args[1]),
+        "atanh" => u.atanh(),
+        "cbrt" => u.cbrt(),
+        "ceil" => u.flat(f64::ceil),
+        "clamp" => {
+            // min(max(u, min), max) like the function itself
+            let (min, max) = (&args[1], &args[2]);
+            let lower = if u.value() < min.value() { min } else { u };
+            match lower.value() > max.value() {
+                true => max.clone(),
+                false => lower.clone(),
+            }
+        }
+        "cos" => u.cos(),
+        "cosh" => u.cosh(),
+        "exp" => u.exp(),
+        "exp2" => u.scale(LN_2).exp(),
+        "floor" => u.flat(f64::floor),
+        "fract" => u.clone() - u.flat(f64::trunc),
+        "hypot" => total(args.iter().map(|a| a.clone() * a.clone())).sqrt(),
+        "ln" => u.ln(),
+        "log2" => u.ln().scale(1.0 / LN_2),
+        "log10" => u.ln().scale(1.0 / LN_10),
+        // the first of equal arguments is taken
+        "max" => args
+            .iter()
+            .reduce(|a, b| if b.value() > a.value() { b } else { a })?
+            .clone(),
+        "mean" => total(args.iter().cloned()).scale(1.0 / args.len() as f64),
+        "median" => {
+            let mut sorted: Vec<&Taylor> = args.iter().collect();
+            sorted.sort_by(|a, b| a.value().total_cmp(&b.value()));
+            let mid = sorted.len() / 2;
+            match sorted.len() % 2 {
+                1 => sorted[mid].clone(),
+                _ => (sorted[mid - 1].clone() + sorted[mid].clone()).scale(0.5),
+            }
+        }
+        "min" => args
+            .iter()
+            .reduce(|a, b| if b.value() < a.value() { b } else { a })?
+            .clone(),
+        "mul_add" => u.clone() * args[1].clone() + args[2].clone(),
+        "pow" => u.pow(&args[1]),
+        "recip" => u.recip(),
+        "round" => u.flat(f64::round),
+        "sign" => u.flat(f64::signum),
+        "sin" => u.sin(),
+        "sinh" => u.sinh(),
+        "sqrt" => u.sqrt(),
+        "stdev" => {
+            let n = args.len() as f64;
+            let mean = total(args.iter().cloned()).scale(1.0 / n);
+            let squares = total(args.iter().map(|a| (a.clone() - mean.clone()).powf(2.0)));
+            squares.scale(1.0 / (n - 1.0)).sqrt()
+        }
+        "sum" => total(args.iter().cloned()),
+        "tan" => u.tan(),
+        "tanh" => u.tanh(),
+        "toDeg" => u.scale(180.0 / std::f64::consts::PI),
+        "toRad" => u.scale(std::f64::consts::PI / 180.0),
+        "trunc" => u.flat(f64::trunc),
+        _ => return None,
+    })
+}
+
+/// Sum of a non-empty sequence
+fn total(terms: impl Iterator<Item = Taylor>) -> Taylor {
+    terms.reduce(|a, b| a + b).unwrap()
+}
+
+impl Add for Taylor {
+    type Output = Taylor;
+
+    fn add(self, rhs: Taylor) -> Taylor {
+        let c = self.coefficients.iter().zip(&rhs.coefficients);
+        Taylor::with_coefficients(c.map(|(a, b)| a + b).collect())
+    }
+}
+
+impl Sub for Taylor {
+    type Output = Taylor;
+
+    fn sub(self, rhs: Taylor) -> Taylor {
+        let c = self.coefficients.iter().zip(&rhs.coefficients);
+        Taylor::with_coefficients(c.map(|(a, b)| a - b).collect())
+    }
+}
+
+impl Mul for Taylor {
+    type Output = Taylor;
+
+    fn mul(self, rhs: Taylor) -> Taylor {
+        let (u, v) = (&self.coefficients, &rhs.coefficients);
+        let w = (0..u.len())
+            .map(|k| (0..=k).map(|j| u[j] * v[k - j]).sum())
+            .collect();
+        Taylor::with_coefficients(w)
+    }
+}
+
+impl Div for Taylor {
+    type Output = Taylor;
+
+    fn div(self, rhs: Taylor) -> Taylor {
+        let (u, v) = (&self.coefficients, &rhs.coefficients);
+        let mut w = vec![0.0; u.len()];
+        for k in 0..u.len() {
+            let sum: f64 = (1..=k).map(|j| v[j] * w[k - j]).sum();
+            w[k] = (u[k] - sum) / v[0];
+        }
+        Taylor::with_coefficients(w)
+    }
+}
+
+impl Neg for Taylor {
+    type Output = Taylor;
+
+    fn neg(self) -> Taylor {
+        self.scale(-1.0)
+    }
+}
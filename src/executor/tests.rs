@@ -1,15 +1,337 @@
 use super::*;
+use crate::diff;
 use crate::lexer;
 use crate::parser;
+use crate::solver_error::SolverError;
+
+fn parse_text(text: &str) -> Node {
+    let tokens = lexer::analyse(text).map(|res| res.unwrap());
+    parser::parse(tokens).unwrap()
+}
 
 #[test]
 fn it_works() {
-    let text = "-2 * e^sin(3.5)";
-
-    let tokens = lexer::analyse(text).map(|res| res.unwrap());
-    let postfix_tokens = parser::parse(tokens).unwrap();
-    let result = eval(&postfix_tokens, None).unwrap();
+    let ast = parse_text("-2 * e^sin(3.5)");
+    let result = eval(&ast, &|_| None).unwrap();
     let expected = -1.40827;
 
     assert!(f64::abs(result - expected) < 0.000_01);
 }
+
+#[test]
+fn nested_calls() {
+    let ast = parse_text("max(1, min(2, 3)) + clamp(max(5, 4), min(1, 0), hypot(3, 4))");
+    assert_eq!(eval(&ast, &|_| None).unwrap(), 7.0);
+}
+
+#[test]
+fn clamp_does_not_panic() {
+    for (text, expected) in [("clamp(1, 3, 2)", 2.0), ("clamp(4, 3, 2)", 2.0)] {
+        assert_eq!(eval(&parse_text(text), &|_| None).unwrap(), expected);
+    }
+    let ast = parse_text("clamp(1, 0/0, 2)");
+    assert!(eval(&ast, &|_| None).unwrap().is_nan());
+}
+
+#[test]
+fn aggregates() {
+    for (text, expected) in [
+        ("max(3, 1, 4, 1, 5)", 5.0),
+        ("min(3, 1, 4, 1, 5)", 1.0),
+        ("max(2)", 2.0),
+        ("hypot(2, 4, 4)", 6.0),
+        ("sum(1, 2, 3, 4)", 10.0),
+        ("mean(1, 2, 3, 4)", 2.5),
+        ("median(5, 1, 3)", 3.0),
+        ("median(4, 1, 3, 2)", 2.5),
+        ("median(2, 1, 2, 2)", 2.0),
+        ("median(3, 1, 1, 3, 1)", 1.0),
+        ("stdev(2, 4, 4, 4, 5, 5, 7, 9)", f64::sqrt(32.0 / 7.0)),
+        ("sum(1, x, 2, 3)", 16.0),
+        // a variable first and three more arguments is `series`
+        ("sum(a, b, c, d)", 8.0),
+    ] {
+        let lookup = |name: &str| match name {
+            "x" => Some(10.0),
+            "a" => Some(1.0),
+            "b" => Some(2.0),
+            "c" => Some(3.0),
+            "d" => Some(4.0),
+            _ => None,
+        };
+        assert_eq!(
+            eval(&parse_text(text), &lookup).unwrap(),
+            expected,
+            "{}",
+            text
+        );
+    }
+    assert!(eval(&parse_text("median(1, 0/0)"), &|_| None)
+        .unwrap()
+        .is_nan());
+}
+
+#[test]
+fn bound_operators() {
+    let env = Env::new().with("n", 5.0).with("k", 10.0);
+    let eval_text = |text: &str| eval(&parse_text(text), &|name| env.get(name));
+    for (text, expected) in [
+        ("prod(k, 1, n, k)", 120.0),
+        ("series(k, 1, n, k) + k", 25.0),
+        ("series(i, 1, 3, series(j, i, 3, i*j))", 25.0),
+        ("series(k, 3, 1, k)", 0.0),
+        ("prod(k, 3, 1, k)", 1.0),
+        ("int(t, 0, 2, t^3)", 4.0),
+        ("int(t, 2, 0, t^3)", -4.0),
+        ("int(n, 0, 1, n) + n", 5.5),
+    ] {
+        let result = eval_text(text).unwrap();
+        assert!(
+            f64::abs(result - expected) < 0.000_000_001,
+            "{}: {}, expected {}",
+            text,
+            result,
+            expected
+        );
+    }
+    let basel = eval_text("series(k, 1, 10000, 1/k^2)").unwrap();
+    assert!(f64::abs(basel - std::f64::consts::PI.powi(2) / 6.0) < 0.000_2);
+    let gauss = eval_text("int(t, -inf, inf, exp(-t^2))").unwrap();
+    assert!(f64::abs(gauss - std::f64::consts::PI.sqrt()) < 0.000_000_001);
+    let large = eval_text("int(t, 0, 1000, t^2)").unwrap();
+    assert!(f64::abs(large / 1e9 * 3.0 - 1.0) < 0.000_000_001);
+
+    for (text, error_type, pos) in [
+        ("series(k, 1, 2.5, k)", ExecutionErrorType::InvalidBounds, 0),
+        ("2*prod(k, 1, inf, k)", ExecutionErrorType::InvalidBounds, 2),
+        (
+            "int(t, 0, 1, series(k, 1, t, k))",
+            ExecutionErrorType::InvalidBounds,
+            13,
+        ),
+        ("int(t, 0, 1, t*y)", ExecutionErrorType::UnknownIdent, 15),
+        ("int(t, 0, 1, 1/t)", ExecutionErrorType::NoConvergence, 0),
+    ] {
+        let error = eval_text(text).unwrap_err();
+        assert_eq!(error.error_type(), &error_type, "{}", text);
+        assert_eq!(error.span().pos, pos, "{}", text);
+    }
+}
+
+#[test]
+fn variables() {
+    let ast = parse_text("r * cos(t)");
+    let env = Env::new().with("r", 2.0).with("t", 0.0);
+    let result = eval(&ast, &|name| env.get(name)).unwrap();
+
+    assert_eq!(result, 2.0);
+
+    let error = eval(&ast, &|name| (name == "r").then_some(1.0)).unwrap_err();
+    assert_eq!(error.error_type(), &ExecutionErrorType::UnknownIdent);
+    assert_eq!(error.text(), "t");
+    assert_eq!(error.help(), Some("the variable is not bound to a value"));
+}
+
+#[test]
+fn taylor_matches_symbolic_derivatives() {
+    let cases = [
+        "abs(x - 1)",
+        "acos(x)",
+        "acosh(x + 1.4)",
+        "asin(x)",
+        "asinh(x)",
+        "atan(x)",
+        "atan2(x, 2*x + 1)",
+        "atan2(1, x)",
+        "atanh(x)",
+        "cbrt(x - 1)",
+        "ceil(x)",
+        "clamp(x^2, 0.1, 1)",
+        "clamp(2, x, x + 1)",
+        "cos(x)",
+        "cosh(x)",
+        "exp(x)",
+        "exp2(x)",
+        "floor(x)",
+        "fract(3*x)",
+        "hypot(x, x^2)",
+        "ln(x)",
+        "log2(x)",
+        "log10(x)",
+        "max(x, 1 - x)",
+        "min(x, 1 - x)",
+        "mul_add(x, x^2, sin(x))",
+        "max(x, 1 - x, x^2)",
+        "min(x, 1 - x, x^2)",
+        "max(x^3)",
+        "hypot(x, 1, x^2)",
+        "sum(x, x^2, sin(x))",
+        "mean(x, x^2)",
+        "median(x, 2*x^2, 1)",
+        "median(x, 0.1, x^2, sin(x))",
+        "stdev(x, x^2, 1)",
+        "pow(x, 3)",
+        "pow(2, x)",
+        "x^x",
+        "x^-2.5",
+        "recip(x)",
+        "round(x)",
+        "sign(x)",
+        "sin(x)",
+        "sinh(x)",
+        "sqrt(x)",
+        "tan(x)",
+        "tanh(x)",
+        "toDeg(x)",
+        "toRad(x)",
+        "trunc(x)",
+        "x/(1 + x^2)",
+        "series(k, 1, 4, x^k/k)",
+        "series(x, 1, 3, x)*x",
+        "int(t, 0, x, exp(-t^2))",
+        "int(t, x^2, 1, t*sin(t))",
+        "int(t, 0, 1, sin(x*t))",
+        "int(x, 0, 1, x^2)*x",
+    ];
+    let x = 0.3;
+    for text in cases {
+        let ast = parse_text(text);
+        let taylor = eval_taylor(&ast, "x", x, 3, &|_| None).unwrap();
+        let mut derivative = ast;
+        for k in 0..=3 {
+            let expected = eval(&derivative, &|_| Some(x)).unwrap();
+            let result = taylor.derivative(k);
+            assert!(
+                f64::abs(result - expected) < 0.000_001 * expected.abs().max(1.0),
+                "{}: derivative {} = {}, expected {}",
+                text,
+                k,
+                result,
+                expected
+            );
+            derivative = diff::simplify(diff::diff(&derivative, "x").unwrap());
+        }
+    }
+}
+
+#[test]
+fn taylor_series() {
+    let ast = parse_text("exp(2*x) * y");
+    let taylor = eval_taylor(&ast, "x", 0.0, 4, &|_| Some(3.0)).unwrap();
+    assert_eq!(taylor.order(), 4);
+    assert_eq!(taylor.derivatives(), vec![3.0, 6.0, 12.0, 24.0, 48.0]);
+
+    let ast = parse_text("x^3");
+    let dual = eval_taylor(&ast, "x", 0.0, 1, &|_| None).unwrap();
+    assert_eq!(dual.coefficients(), &[0.0, 0.0]);
+
+    let ast = parse_text("median(x, 2*x^2, 1)");
+    let taylor = eval_taylor(&ast, "x", 0.6, 2, &|_| None).unwrap();
+    assert_eq!(taylor.derivatives(), vec![0.72, 2.4, 4.0]);
+
+    // products have no symbolic derivative
+    let ast = parse_text("prod(k, 1, 3, x + k)");
+    let taylor = eval_taylor(&ast, "x", 0.0, 3, &|_| None).unwrap();
+    assert_eq!(taylor.derivatives(), vec![6.0, 11.0, 12.0, 6.0]);
+
+    let ast = parse_text("int(t, x, 2*x, t*x)");
+    let error = eval_taylor(&ast, "x", 1.0, 1, &|_| None).unwrap_err();
+    // the same error as for functions without a symbolic derivative
+    assert_eq!(error.code(), "E401");
+
+    let ast = parse_text("x^3");
+    let error = eval_taylor(&ast, "t", 0.0, 1, &|_| None).unwrap_err();
+    assert_eq!(error.code(), "E301");
+}
+
+#[test]
+fn complex_numbers() {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    fn eval_text(text: &str) -> Result<Complex, ExecutionError> {
+        eval_complex(&parse_text(text), &|name| match name {
+            "i" => Some(I),
+            "z" => Some(Complex::new(1.0, -2.0)),
+            _ => None,
+        })
+    }
+    let close = |z: Complex, re: f64, im: f64| (z - Complex::new(re, im)).abs() < 1e-12;
+
+    assert_eq!(eval_text("sqrt(-1)").unwrap(), I);
+    assert_eq!(eval_text("sqrt(-4)").unwrap(), Complex::new(0.0, 2.0));
+    let ln = eval_text("ln(-2)").unwrap();
+    assert!(close(ln, f64::ln(2.0), PI), "{}", ln);
+    let euler = eval_text("e^(i*pi)").unwrap();
+    assert!(close(euler, -1.0, 0.0), "{}", euler);
+    assert_eq!(
+        eval_text("(1 + 2*i)*(3 - i)").unwrap(),
+        Complex::new(5.0, 5.0)
+    );
+    assert!(close(eval_text("asin(sin(z))").unwrap(), 1.0, -2.0));
+    assert!(close(eval_text("z^0.5*z^0.5").unwrap(), 1.0, -2.0));
+    assert!(close(eval_text("cos(z)^2 + sin(z)^2").unwrap(), 1.0, 0.0));
+    assert_eq!(
+        eval_text("i*inf").unwrap(),
+        Complex::new(0.0, f64::INFINITY)
+    );
+    assert_eq!(eval_text("i/0").unwrap(), Complex::new(0.0, f64::INFINITY));
+
+    // real arguments lie on the upper side of the branch cuts as in C99
+    let acosh2 = f64::acosh(2.0);
+    let atanh2 = 0.5 * f64::ln(3.0);
+    for (text, re, im) in [
+        ("asin(2)", FRAC_PI_2, acosh2),
+        ("asin(-2)", -FRAC_PI_2, acosh2),
+        ("acos(2)", 0.0, -acosh2),
+        ("acos(-2)", PI, -acosh2),
+        ("atanh(2)", atanh2, FRAC_PI_2),
+        ("atanh(-2)", -atanh2, FRAC_PI_2),
+        ("acosh(-2)", acosh2, PI),
+        ("acosh(0.5)", 0.0, f64::acos(0.5)),
+        ("ln(-1)", 0.0, PI),
+    ] {
+        let value = eval_text(text).unwrap();
+        assert!(close(value, re, im), "{}: {}", text, value);
+    }
+
+    // real arguments give the same values as on real numbers
+    for text in [
+        "2 + 2*sin(3^-3)",
+        "max(1, 2, hypot(3, 4)) + atan2(1, 2)",
+        "floor(2.5)",
+    ] {
+        let ast = parse_text(text);
+        let real = eval(&ast, &|_| None).unwrap();
+        assert_eq!(eval_text(text).unwrap(), Complex::from(real), "{}", text);
+    }
+
+    // `i` is an ordinary variable for bound operators
+    assert_eq!(eval_text("series(i, 1, 3, i)").unwrap(), Complex::from(6.0));
+    assert!(close(eval_text("int(t, 0, 1, 2*t*i)").unwrap(), 0.0, 1.0));
+
+    for (text, error_type, pos) in [
+        ("1 + floor(i)", ExecutionErrorType::NotReal, 4),
+        ("series(k, 1, i, k)", ExecutionErrorType::NotReal, 0),
+        ("z + w", ExecutionErrorType::UnknownIdent, 4),
+    ] {
+        let error = eval_text(text).unwrap_err();
+        assert_eq!(error.error_type(), &error_type, "{}", text);
+        assert_eq!(error.span().pos, pos, "{}", text);
+    }
+    assert_eq!(eval_text("floor(i)").unwrap_err().code(), "E305");
+}
+
+#[test]
+fn complex_formatting() {
+    assert_eq!(Complex::new(1.5, -2.0).to_string(), "1.5 - 2i");
+    assert_eq!(Complex::new(1.5, 2.0).to_string(), "1.5 + 2i");
+    assert_eq!(Complex::new(0.0, -1.0).to_string(), "-1i");
+    assert_eq!(Complex::from(-3.0).to_string(), "-3");
+    assert_eq!(
+        Complex::new(0.0, 2.0).polar().to_string(),
+        "2∠1.5707963267948966"
+    );
+    assert_eq!(Complex::from(-1.0).to_polar(), (1.0, std::f64::consts::PI));
+    let z = Complex::from_polar(2.0, 0.5);
+    assert!((z.abs() - 2.0).abs() < 1e-15 && (z.arg() - 0.5).abs() < 1e-15);
+}
@@ -0,0 +1,312 @@
+use std::fmt::{self, Display};
+
+use crate::ast::Node;
+use crate::context::Context;
+use crate::executor::{Complex, Env, Taylor, I};
+use crate::jit::Compiled;
+use crate::solver_error::Error;
+use crate::vm::Program;
+use crate::{diff, executor, lexer, optimizer, parser};
+
+/// Parsed expression ready to be evaluated any number of times.
+#[derive(Clone, Debug)]
+pub struct Expression {
+    source: String,
+    ast: Node,
+    /// Tree the expression is evaluated with, see [`optimizer::optimize`]
+    optimized: Node,
+    /// Optimized tree compiled for evaluation
+    program: Program,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        Self::parse_with(source, &Context::new())
+    }
+
+    /// Parses expression which may use constants and functions of `context`.
+    /// The expression does not depend on `context` afterwards
+    pub fn parse_with(source: &str, context: &Context) -> Result<Self, Error> {
+        let tokens = lexer::analyse_with(source, context).collect::<Result<Vec<_>, _>>()?;
+        let ast = parser::parse(tokens.into_iter())?;
+        Ok(Self::new(source.to_string(), ast))
+    }
+
+    fn new(source: String, ast: Node) -> Self {
+        let optimized = optimizer::optimize(ast.clone());
+        Self {
+            program: Program::compile(&optimized),
+            optimized,
+            source,
+            ast,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn ast(&self) -> &Node {
+        &self.ast
+    }
+
+    /// Evaluates expression with variables bound in `env`
+    pub fn eval(&self, env: &Env) -> Result<f64, Error> {
+        let lookup = |name: &str| env.get(name);
+        // the optimized program may run into another error first, the error
+        // of the expression as written is reported
+        let value = self
+            .program
+            .eval(&lookup, &mut self.program.registers())
+            .or_else(|_| executor::eval(&self.ast, &lookup))?;
+        Ok(value)
+    }
+
+    /// Evaluates expression on complex numbers with variables bound in `env`.
+    /// `i` is the imaginary unit unless an operator like `series` binds it
+    pub fn eval_complex(&self, env: &Env<Complex>) -> Result<Complex, Error> {
+        let lookup = |name: &str| match name {
+            "i" => Some(I),
+            _ => env.get(name),
+        };
+        Ok(executor::eval_complex(&self.ast, &lookup)?)
+    }
+
+    /// Complex version of [`Self::closure`] for [`solvers::muller`]
+    ///
+    /// [`solvers::muller`]: crate::solvers::muller
+    pub fn complex_closure<'a>(
+        &'a self,
+        var: &'a str,
+        env: &'a Env<Complex>,
+    ) -> Result<impl Fn(Complex) -> Complex + 'a, Error> {
+        let eval = move |z| {
+            executor::eval_complex(&self.ast, &|name| match name {
+                name if name == var => Some(z),
+                "i" => Some(I),
+                _ => env.get(name),
+            })
+        };
+        self.program
+            .check_vars(&|name| match name == var || name == "i" {
+                true => Some(0.0),
+                false => env.get(name).map(|z| z.re),
+            })?;
+        Ok(move |z| eval(z).unwrap_or(Complex::from(f64::NAN)))
+    }
+
+    /// Evaluates expression for every row of `columns`, variables without a
+    /// column are taken from `env`. Rows which can not be computed are NaN,
+    /// see [`Program::eval_batch`]
+    pub fn eval_batch(&self, columns: &[(&str, &[f64])], env: &Env) -> Result<Vec<f64>, Error> {
+        self.eval_batch_parallel(columns, env, 1)
+    }
+
+    /// Same as [`Self::eval_batch`] with the rows split between `threads`
+    /// threads
+    pub fn eval_batch_parallel(
+        &self,
+        columns: &[(&str, &[f64])],
+        env: &Env,
+        threads: usize,
+    ) -> Result<Vec<f64>, Error> {
+        let lookup = |name: &str| env.get(name);
+        let rows = columns.first().map_or(1, |(_, column)| column.len());
+        let mut out = vec![0.0; rows];
+        match threads {
+            0 | 1 => self.program.eval_batch(columns, &lookup, &mut out)?,
+            _ => self
+                .program
+                .eval_batch_parallel(columns, &lookup, &mut out, threads)?,
+        }
+        Ok(out)
+    }
+
+    /// Simplified derivative with respect to `var`. It keeps the source of
+    /// this expression, so errors point to the differentiated part of it
+    pub fn derivative(&self, var: &str) -> Result<Self, Error> {
+        let ast = diff::simplify(diff::diff(&self.ast, var)?);
+        Ok(Self::new(self.source.clone(), ast))
+    }
+
+    /// Expression with constant subexpressions folded and identities like
+    /// `x*1` removed, see [`optimizer::fold`]
+    pub fn simplify(&self) -> Self {
+        Self::new(self.source.clone(), optimizer::fold(self.ast.clone()))
+    }
+
+    /// Value and derivatives up to `order` with respect to `var` at `x`
+    /// computed with automatic differentiation, other variables are taken
+    /// from `env`
+    pub fn taylor(&self, var: &str, x: f64, order: usize, env: &Env) -> Result<Taylor, Error> {
+        let lookup = |name: &str| env.get(name);
+        executor::eval_taylor(&self.ast, var, x, order, &lookup)
+    }
+
+    /// Returns expression as a function of variable `var` to be handed to
+    /// `solvers`, other variables are taken from `env`. See [`Self::compile`]
+    pub fn closure<'a>(
+        &'a self,
+        var: &'a str,
+        env: &'a Env,
+    ) -> Result<impl Fn(f64) -> f64 + 'a, Error> {
+        let compiled = self.compile(var, env)?;
+        Ok(move |x| compiled.call(x))
+    }
+
+    /// Compiles expression as a function of variable `var`, to native code
+    /// with the `jit` feature. Other variables are fixed to their values in
+    /// `env`.
+    ///
+    /// Only the variables are checked here, errors depending on the value of
+    /// `var` like bounds of `series` or `int` make the function NaN there.
+    pub fn compile(&self, var: &str, env: &Env) -> Result<Compiled, Error> {
+        let lookup = |name: &str| env.get(name);
+        self.program.check_vars(&|name| match name == var {
+            true => Some(0.0),
+            false => lookup(name),
+        })?;
+        Ok(Compiled::new(&self.optimized, var, &lookup))
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.ast)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver_error::SolverError;
+    use crate::solvers::{root, RootMethod};
+
+    #[test]
+    fn it_works() {
+        let expr = Expression::parse("x^2 - 2").unwrap();
+
+        assert_eq!(expr.eval(&Env::new().with("x", 3.0)).unwrap(), 7.0);
+        assert!(matches!(expr.eval(&Env::new()), Err(Error::Execution(_))));
+
+        let env = Env::new();
+        let f = expr.closure("x", &env).unwrap();
+        let r = root(f, 0.0, 2.0, 0.000_000_1, None, RootMethod::default())
+            .ok()
+            .unwrap();
+        assert!(f64::abs(r - std::f64::consts::SQRT_2) < 0.000_001);
+    }
+
+    #[test]
+    fn errors_are_returned() {
+        assert!(matches!(Expression::parse("2 $ 3"), Err(Error::Analyse(_))));
+        assert!(matches!(
+            Expression::parse("(2 + 3"),
+            Err(Error::Parsing(_))
+        ));
+        assert!(matches!(Expression::parse("2 3"), Err(Error::Parsing(_))));
+        assert!(matches!(
+            Expression::parse("2 * y")
+                .unwrap()
+                .closure("x", &Env::new()),
+            Err(Error::Execution(_))
+        ));
+    }
+
+    #[test]
+    fn folding_does_not_panic() {
+        let expr = Expression::parse("clamp(1, 3, 2) + clamp(x, 3, 2)").unwrap();
+        assert_eq!(expr.simplify().to_string(), "2 + clamp(x, 3, 2)");
+        assert_eq!(expr.eval(&Env::new().with("x", 0.0)).unwrap(), 4.0);
+        let env = Env::new();
+        let f = expr.closure("x", &env).unwrap();
+        assert_eq!(f(5.0), 4.0);
+    }
+
+    #[test]
+    fn bounds_depending_on_var() {
+        use crate::solvers::{integral, IntegralMethod};
+
+        let env = Env::new();
+        let expr = Expression::parse("int(t, 1, x, 1/t) - 1").unwrap();
+        let f = expr.closure("x", &env).unwrap();
+        let r = root(f, 2.0, 3.0, 1e-9, None, RootMethod::default())
+            .ok()
+            .unwrap();
+        assert!(f64::abs(r - std::f64::consts::E) < 1e-6);
+
+        let expr = Expression::parse("series(k, 1, floor(1/x), k)").unwrap();
+        let f = expr.closure("x", &env).unwrap();
+        assert!(f(0.0).is_nan());
+        let report = integral(f, 0.5, 1.0, 1e-9, None, IntegralMethod::default());
+        assert!(f64::abs(report.ok().unwrap() - 0.5) < 1e-6);
+
+        let expr = Expression::parse("series(k, 1, 1/x, k)").unwrap();
+        let env = Env::new();
+        let f = expr.complex_closure("x", &env).unwrap();
+        assert!(f(Complex::default()).re.is_nan());
+        assert_eq!(f(Complex::from(1.0 / 3.0)), Complex::from(6.0));
+    }
+
+    #[test]
+    fn named_variables() {
+        let expr = Expression::parse("a*x^2 + b*x + c").unwrap();
+        let env = Env::from_iter([("a", 1.0), ("b", -3.0), ("c", 2.0)]);
+
+        assert_eq!(expr.eval(&env.clone().with("x", 3.0)).unwrap(), 2.0);
+
+        let f = expr.closure("x", &env).unwrap();
+        assert_eq!(f(1.0), 0.0);
+        assert_eq!(f(0.0), 2.0);
+
+        let error = expr.closure("t", &env).err().unwrap();
+        assert_eq!(error.code(), "E301");
+        assert_eq!(error.span(), crate::tokens::Span::new(2, 1));
+    }
+
+    #[test]
+    fn complex_numbers() {
+        use crate::executor::{Complex, I};
+
+        let expr = Expression::parse("sqrt(x) + i").unwrap();
+        let env = Env::new().with("x", Complex::from(-4.0));
+        assert_eq!(expr.eval_complex(&env).unwrap(), Complex::new(0.0, 3.0));
+        let root = Expression::parse("sqrt(x)").unwrap();
+        assert!(root.eval(&Env::new().with("x", -4.0)).unwrap().is_nan());
+        let env = Env::new().with("x", Complex::from(-4.0));
+        assert_eq!(root.eval_complex(&env).unwrap(), Complex::new(0.0, 2.0));
+
+        let quadratic = Expression::parse("z^2 + a").unwrap();
+        let env = Env::new().with("a", Complex::from(1.0));
+        let f = quadratic.complex_closure("z", &env).unwrap();
+        assert_eq!(f(I), Complex::default());
+
+        let error = expr.complex_closure("z", &Env::new()).err().unwrap();
+        assert_eq!(error.code(), "E301");
+    }
+
+    #[test]
+    fn batches() {
+        let expr = Expression::parse("a*x + y").unwrap();
+        let env = Env::new().with("a", 2.0);
+        let (xs, ys) = ([1.0, 2.0, 3.0], [0.5, 0.0, -1.0]);
+        let columns: [(&str, &[f64]); 2] = [("x", &xs), ("y", &ys)];
+        assert_eq!(expr.eval_batch(&columns, &env).unwrap(), [2.5, 4.0, 5.0]);
+        assert_eq!(
+            expr.eval_batch_parallel(&columns, &env, 2).unwrap(),
+            [2.5, 4.0, 5.0]
+        );
+
+        // rows which can not be computed do not fail the others
+        let expr = Expression::parse("series(k, 1, 1/x, k)").unwrap();
+        let values = expr.eval_batch(&[("x", &[0.5, 0.3, 1.0])], &env).unwrap();
+        assert_eq!(values[0], 3.0);
+        assert!(values[1].is_nan());
+        assert_eq!(values[2], 1.0);
+
+        let expr = Expression::parse("a*x + y").unwrap();
+        let error = expr.eval_batch(&columns[..1], &env).unwrap_err();
+        assert_eq!(error.code(), "E301");
+        assert_eq!(error.span(), crate::tokens::Span::new(6, 1));
+    }
+}
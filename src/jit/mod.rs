@@ -0,0 +1,92 @@
+use std::cell::RefCell;
+
+use crate::ast::Node;
+use crate::vm::Program;
+
+#[cfg(feature = "jit")]
+mod native;
+#[cfg(test)]
+mod tests;
+
+/// Expression compiled as a function of one variable, the other variables
+/// are fixed at compilation. With the `jit` feature it is compiled to native
+/// code, otherwise and for expressions using `int` it runs on the
+/// [`Program`] interpreter
+#[derive(Debug)]
+pub struct Compiled {
+    code: Code,
+}
+
+#[derive(Debug)]
+enum Code {
+    #[cfg(feature = "jit")]
+    Native(Box<native::Native>),
+    Program {
+        program: Program,
+        registers: RefCell<Vec<f64>>,
+        slot: Option<usize>,
+    },
+}
+
+impl Compiled {
+    /// Compiles optimized `node` as a function of `var`, other variables are
+    /// resolved with `lookup` and those missing are NaN
+    pub(crate) fn new<F>(node: &Node, var: &str, lookup: &F) -> Self
+    where
+        F: Fn(&str) -> Option<f64> + ?Sized,
+    {
+        #[cfg(feature = "jit")]
+        if let Some(native) = native::Native::compile(node, var, lookup) {
+            return Self {
+                code: Code::Native(Box::new(native)),
+            };
+        }
+
+        let program = Program::compile(node);
+        let mut registers = program.registers();
+        for (name, reg) in program.vars() {
+            registers[reg] = lookup(name).unwrap_or(f64::NAN);
+        }
+        let slot = program.slot(var);
+        Self {
+            code: Code::Program {
+                program,
+                registers: RefCell::new(registers),
+                slot,
+            },
+        }
+    }
+
+    /// Value of the expression at `var = x`, NaN where it can not be computed
+    pub fn call(&self, x: f64) -> f64 {
+        match &self.code {
+            #[cfg(feature = "jit")]
+            Code::Native(native) => native.call(x),
+            Code::Program {
+                program,
+                registers,
+                slot,
+            } => {
+                let mut registers = registers.borrow_mut();
+                if let Some(slot) = *slot {
+                    registers[slot] = x;
+                }
+                program.run(&mut registers).unwrap_or(f64::NAN)
+            }
+        }
+    }
+
+    /// Native code of the function if it was compiled to it
+    ///
+    /// # Safety
+    ///
+    /// The code is freed when `self` is dropped, the pointer must not be
+    /// called after that
+    pub unsafe fn native(&self) -> Option<extern "C" fn(f64) -> f64> {
+        match &self.code {
+            #[cfg(feature = "jit")]
+            Code::Native(native) => Some(native.function()),
+            Code::Program { .. } => None,
+        }
+    }
+}
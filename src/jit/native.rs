@@ -0,0 +1,340 @@
+use std::fmt::{self, Debug};
+use std::mem::{self, ManuallyDrop};
+use std::slice;
+use std::sync::Arc;
+
+use cranelift_codegen::ir::condcodes::FloatCC;
+use cranelift_codegen::ir::Value;
+use cranelift_codegen::ir::{
+    types, AbiParam, InstBuilder, Signature, StackSlotData, StackSlotKind,
+};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Linkage, Module};
+
+use crate::ast::{BinaryOp, BoundOp, Node, NodeKind, UnaryOp};
+use crate::context::NativeFunction;
+use crate::optimizer::is_builtin;
+
+/// Native code of an expression
+pub(super) struct Native {
+    module: ManuallyDrop<JITModule>,
+    function: extern "C" fn(f64) -> f64,
+    /// Functions called by the code through pointers
+    _functions: Vec<Arc<NativeFunction>>,
+}
+
+impl Native {
+    /// Compiles `node` to native code, `None` if it uses `int` or the host
+    /// is not supported
+    pub(super) fn compile<F>(node: &Node, var: &str, lookup: &F) -> Option<Self>
+    where
+        F: Fn(&str) -> Option<f64> + ?Sized,
+    {
+        let mut flags = settings::builder();
+        flags.set("opt_level", "speed").ok()?;
+        let isa = cranelift_native::builder()
+            .ok()?
+            .finish(settings::Flags::new(flags))
+            .ok()?;
+        let mut module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+        let mut ctx = module.make_context();
+        ctx.func.signature.params.push(AbiParam::new(types::F64));
+        ctx.func.signature.returns.push(AbiParam::new(types::F64));
+
+        let mut builder_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut ctx.func, &mut builder_ctx);
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        let mut lowering = Lowering {
+            x: builder.block_params(entry)[0],
+            builder,
+            var,
+            lookup,
+            scopes: Vec::new(),
+            variables: 0,
+            functions: Vec::new(),
+        };
+        let result = lowering.lower(node);
+        let Lowering {
+            mut builder,
+            functions,
+            ..
+        } = lowering;
+        builder.ins().return_(&[result?]);
+        builder.seal_all_blocks();
+        builder.finalize();
+
+        let id = module
+            .declare_function("expression", Linkage::Local, &ctx.func.signature)
+            .ok()?;
+        module.define_function(id, &mut ctx).ok()?;
+        module.clear_context(&mut ctx);
+        module.finalize_definitions().ok()?;
+        let code = module.get_finalized_function(id);
+        // SAFETY: the function was built with the signature of `f64 -> f64`
+        // and the default calling convention of the host, which is "C"
+        let function = unsafe { mem::transmute::<*const u8, extern "C" fn(f64) -> f64>(code) };
+        Some(Self {
+            module: ManuallyDrop::new(module),
+            function,
+            _functions: functions,
+        })
+    }
+
+    pub(super) fn call(&self, x: f64) -> f64 {
+        (self.function)(x)
+    }
+
+    pub(super) fn function(&self) -> extern "C" fn(f64) -> f64 {
+        self.function
+    }
+}
+
+impl Drop for Native {
+    fn drop(&mut self) {
+        // SAFETY: the code is not running as `self` is borrowed by calls, and
+        // callers of the unsafe `Compiled::native` do not call the pointer
+        // once `self` is dropped
+        unsafe { ManuallyDrop::take(&mut self.module).free_memory() }
+    }
+}
+
+impl Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Native")
+            .field("function", &self.function)
+            .finish()
+    }
+}
+
+extern "C" fn pow(x: f64, y: f64) -> f64 {
+    x.powf(y)
+}
+
+/// Calls `func` with `len` arguments at `args`
+extern "C" fn call(func: *const NativeFunction, args: *const f64, len: usize) -> f64 {
+    // SAFETY: `func` is kept alive by `Native` and `args` points to a stack
+    // slot of `len` values
+    let (func, args) = unsafe { (&*func, slice::from_raw_parts(args, len)) };
+    func.call(args)
+}
+
+struct Lowering<'a, F: ?Sized> {
+    builder: FunctionBuilder<'a>,
+    /// Parameter of the function
+    x: Value,
+    var: &'a str,
+    lookup: &'a F,
+    /// Variables bound by operators and shared subexpressions, innermost last
+    scopes: Vec<(String, Variable)>,
+    variables: u32,
+    functions: Vec<Arc<NativeFunction>>,
+}
+
+impl<F> Lowering<'_, F>
+where
+    F: Fn(&str) -> Option<f64> + ?Sized,
+{
+    fn number(&mut self, value: f64) -> Value {
+        self.builder.ins().f64const(value)
+    }
+
+    fn variable(&mut self, value: Value) -> Variable {
+        let variable = Variable::from_u32(self.variables);
+        self.variables += 1;
+        self.builder.declare_var(variable, types::F64);
+        self.builder.def_var(variable, value);
+        variable
+    }
+
+    fn lower(&mut self, node: &Node) -> Option<Value> {
+        Some(match &node.kind {
+            NodeKind::Number(n) => self.number(*n),
+            NodeKind::Const { value, .. } => self.number(*value),
+            NodeKind::Var(name) => match self.scopes.iter().rev().find(|(var, _)| var == name) {
+                Some(&(_, variable)) => self.builder.use_var(variable),
+                None if name == self.var => self.x,
+                None => self.number((self.lookup)(name).unwrap_or(f64::NAN)),
+            },
+            NodeKind::Unary {
+                op: UnaryOp::Neg,
+                operand,
+            } => {
+                let operand = self.lower(operand)?;
+                self.builder.ins().fneg(operand)
+            }
+            NodeKind::Binary {
+                op: BinaryOp::Pow,
+                lhs,
+                rhs,
+            } if matches!(rhs.kind, NodeKind::Number(2.0)) => {
+                // squares are exact, so they do not need `powf`
+                let lhs = self.lower(lhs)?;
+                self.builder.ins().fmul(lhs, lhs)
+            }
+            NodeKind::Binary { op, lhs, rhs } => {
+                let (lhs, rhs) = (self.lower(lhs)?, self.lower(rhs)?);
+                let ins = self.builder.ins();
+                match op {
+                    BinaryOp::Add => ins.fadd(lhs, rhs),
+                    BinaryOp::Sub => ins.fsub(lhs, rhs),
+                    BinaryOp::Mul => ins.fmul(lhs, rhs),
+                    BinaryOp::Div => ins.fdiv(lhs, rhs),
+                    BinaryOp::Pow => self.call_pointer(pow as *const () as usize, &[lhs, rhs], 2),
+                }
+            }
+            NodeKind::Call { name, func, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.lower(arg))
+                    .collect::<Option<Vec<_>>>()?;
+                self.call(name, func, &args)
+            }
+            NodeKind::Bound {
+                op: op @ (BoundOp::Series | BoundOp::Prod),
+                var,
+                from,
+                to,
+                body,
+            } => {
+                let (from, to) = (self.lower(from)?, self.lower(to)?);
+                self.fold(*op, var, from, to, body)?
+            }
+            NodeKind::Bound { .. } => return None,
+            NodeKind::Let { var, value, body } => {
+                let value = self.lower(value)?;
+                let variable = self.variable(value);
+                self.scopes.push((var.clone(), variable));
+                let body = self.lower(body);
+                self.scopes.pop();
+                body?
+            }
+        })
+    }
+
+    /// Calls function `name`, some of the built-in ones are instructions
+    fn call(&mut self, name: &str, func: &Arc<NativeFunction>, args: &[Value]) -> Value {
+        if is_builtin(name, func) {
+            let ins = self.builder.ins();
+            match (name, args) {
+                ("abs", &[x]) => return ins.fabs(x),
+                ("sqrt", &[x]) => return ins.sqrt(x),
+                ("floor", &[x]) => return ins.floor(x),
+                ("ceil", &[x]) => return ins.ceil(x),
+                ("trunc", &[x]) => return ins.trunc(x),
+                _ => {}
+            }
+        }
+
+        // arguments are passed in a stack slot
+        let size = (args.len() * mem::size_of::<f64>()) as u32;
+        let slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            size,
+            3,
+        ));
+        for (i, &arg) in args.iter().enumerate() {
+            let offset = (i * mem::size_of::<f64>()) as i32;
+            self.builder.ins().stack_store(arg, slot, offset);
+        }
+        let pointer = self.pointer_type();
+        let addr = self.builder.ins().stack_addr(pointer, slot, 0);
+        let func_ptr = self.builder.ins().iconst(pointer, Arc::as_ptr(func) as i64);
+        let len = self.builder.ins().iconst(pointer, args.len() as i64);
+        if !self.functions.iter().any(|f| Arc::ptr_eq(f, func)) {
+            self.functions.push(func.clone());
+        }
+        self.call_pointer(call as *const () as usize, &[func_ptr, addr, len], 0)
+    }
+
+    /// Calls an `extern "C"` function at `address` returning `f64`, its first
+    /// `floats` arguments are `f64` and the rest are pointer sized
+    fn call_pointer(&mut self, address: usize, args: &[Value], floats: usize) -> Value {
+        let pointer = self.pointer_type();
+        let mut signature = Signature::new(self.builder.func.signature.call_conv);
+        for i in 0..args.len() {
+            let ty = if i < floats { types::F64 } else { pointer };
+            signature.params.push(AbiParam::new(ty));
+        }
+        signature.returns.push(AbiParam::new(types::F64));
+        let signature = self.builder.import_signature(signature);
+        let callee = self.builder.ins().iconst(pointer, address as i64);
+        let inst = self.builder.ins().call_indirect(signature, callee, args);
+        self.builder.inst_results(inst)[0]
+    }
+
+    fn pointer_type(&self) -> types::Type {
+        match mem::size_of::<usize>() {
+            4 => types::I32,
+            _ => types::I64,
+        }
+    }
+
+    /// Loop adding up or multiplying `body` over `var` from `from` to `to`,
+    /// it is NaN unless the bounds are integers
+    fn fold(
+        &mut self,
+        op: BoundOp,
+        var: &str,
+        from: Value,
+        to: Value,
+        body: &Node,
+    ) -> Option<Value> {
+        let valid = {
+            let is_integer = |lowering: &mut Self, n: Value| {
+                let ins = lowering.builder.ins();
+                let floor = ins.floor(n);
+                let integral = lowering.builder.ins().fcmp(FloatCC::Equal, floor, n);
+                // infinities differ from themselves by NaN
+                let difference = lowering.builder.ins().fsub(n, n);
+                let zero = lowering.number(0.0);
+                let finite = lowering
+                    .builder
+                    .ins()
+                    .fcmp(FloatCC::Equal, difference, zero);
+                lowering.builder.ins().band(integral, finite)
+            };
+            let (from, to) = (is_integer(self, from), is_integer(self, to));
+            self.builder.ins().band(from, to)
+        };
+
+        let init = self.number(if op == BoundOp::Series { 0.0 } else { 1.0 });
+        let acc = self.variable(init);
+        let k = self.variable(from);
+        let header = self.builder.create_block();
+        let looped = self.builder.create_block();
+        let exit = self.builder.create_block();
+        self.builder.append_block_param(exit, types::F64);
+        let nan = self.number(f64::NAN);
+        self.builder.ins().brif(valid, header, &[], exit, &[nan]);
+
+        self.builder.switch_to_block(header);
+        let current = self.builder.use_var(k);
+        let done = self.builder.ins().fcmp(FloatCC::GreaterThan, current, to);
+        let total = self.builder.use_var(acc);
+        self.builder.ins().brif(done, exit, &[total], looped, &[]);
+
+        self.builder.switch_to_block(looped);
+        self.scopes.push((var.to_string(), k));
+        let value = self.lower(body);
+        self.scopes.pop();
+        let value = value?;
+        let total = self.builder.use_var(acc);
+        let total = match op {
+            BoundOp::Series => self.builder.ins().fadd(total, value),
+            _ => self.builder.ins().fmul(total, value),
+        };
+        self.builder.def_var(acc, total);
+        let current = self.builder.use_var(k);
+        let one = self.number(1.0);
+        let next = self.builder.ins().fadd(current, one);
+        self.builder.def_var(k, next);
+        self.builder.ins().jump(header, &[]);
+
+        self.builder.switch_to_block(exit);
+        Some(self.builder.block_params(exit)[0])
+    }
+}
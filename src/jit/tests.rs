@@ -0,0 +1,67 @@
+use crate::context::Arity;
+use crate::solver_error::SolverError;
+use crate::{Context, Env, Expression};
+
+/// Compares the compiled functions with the interpreter
+#[test]
+fn agrees_with_interpreter() {
+    let mut context = Context::new();
+    context
+        .register_function("twice", Arity::AtLeast(1), |args| 2.0 * args[0])
+        .unwrap();
+    let env = Env::new().with("a", 1.5).with("n", 6.0);
+    for text in [
+        "a*x^2 - 2*x + 1",
+        "-x*a + -(-x)/3",
+        "sqrt(abs(x)) + floor(3*x) - ceil(x) + trunc(-x) + round(x)",
+        "max(x, a, 2*x - 1) + atan2(x, a) + exp(sin(x))",
+        "twice(x) + twice(twice(x, 1), 2, 3)",
+        "series(k, 1, n, x^k/k)",
+        "prod(k, 1, n, 1 + x/k) + prod(k, 3, 1, k)",
+        "series(k, 1, 3, series(j, k, n, j*x))",
+        "series(k, 1, x, k)",
+        "sin(x)^2 + sin(x)^2*cos(x) + series(k, 1, 3, (x + k)^2 + sqrt(x + k))",
+        "int(t, 0, x, exp(-t^2))",
+    ] {
+        let expr = Expression::parse_with(text, &context).unwrap();
+        let compiled = expr.compile("x", &env).unwrap();
+        for x in [-1.5, 0.0, 0.3, 2.0, 2.5] {
+            let value = compiled.call(x);
+            let expected = expr.eval(&env.clone().with("x", x)).unwrap_or(f64::NAN);
+            assert!(
+                value == expected
+                    || (value.is_nan() && expected.is_nan())
+                    || f64::abs(value - expected) < 1e-12 * expected.abs().max(1.0),
+                "{} at {}: {}, expected {}",
+                text,
+                x,
+                value,
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn native_code() {
+    let expr = Expression::parse("x^2 + 1").unwrap();
+    let compiled = expr.compile("x", &Env::new()).unwrap();
+    // SAFETY: the pointer is only called while `compiled` is alive
+    let native = unsafe { compiled.native() };
+    assert_eq!(native.is_some(), cfg!(feature = "jit"));
+    if let Some(f) = native {
+        assert_eq!(f(3.0), 10.0);
+    }
+
+    // `int` runs on the interpreter
+    let expr = Expression::parse("int(t, 0, x, t)").unwrap();
+    let compiled = expr.compile("x", &Env::new()).unwrap();
+    assert!(unsafe { compiled.native() }.is_none());
+    assert!(f64::abs(compiled.call(2.0) - 2.0) < 1e-9);
+
+    let error = Expression::parse("x + y")
+        .unwrap()
+        .compile("x", &Env::new())
+        .unwrap_err();
+    assert_eq!(error.code(), "E301");
+}
@@ -1,49 +1,41 @@
-use crate::colors::{bold_red, bold_yellow};
-use crate::solver_error::SolverError;
+use crate::solver_error::{Diagnostic, ErrorType};
 
-#[derive(Clone, Debug)]
-pub struct AnalyseError<'src> {
-    pos: usize,
-    text: &'src str,
-    error_type: AnalyseErrorType,
-}
+pub type AnalyseError = Diagnostic<AnalyseErrorType>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum AnalyseErrorType {
     WrongNumber,
-    UnknownIdent,
+    UnknownFunction,
     UnknownSymbol,
 }
 
-impl<'src> AnalyseError<'src> {
-    pub fn new(pos: usize, text: &'src str, error_type: AnalyseErrorType) -> Self {
-        Self {
-            pos,
-            text,
-            error_type,
+impl ErrorType for AnalyseErrorType {
+    fn code(&self) -> &'static str {
+        use AnalyseErrorType::*;
+        match self {
+            WrongNumber => "E101",
+            UnknownFunction => "E102",
+            UnknownSymbol => "E103",
         }
     }
-}
 
-impl SolverError for AnalyseError<'_> {
-    fn display_solver_error(&self, source: &str) -> String {
+    fn message(&self) -> &'static str {
         use AnalyseErrorType::*;
-
-        let msg = match self.error_type {
+        match self {
             WrongNumber => "could not parse number",
-            UnknownIdent => "unknown identifier",
+            UnknownFunction => "unknown function",
             UnknownSymbol => "unknown symbol",
-        };
-
-        let brief = format!("{} `{}` at {}", msg, self.text, self.pos);
-        let cursor = " ".repeat(self.pos) + &"^".repeat(self.text.len());
+        }
+    }
 
-        format!(
-            "{}: {}\n{}\n{}",
-            bold_red("error"),
-            brief,
-            source,
-            bold_yellow(cursor),
-        )
+    fn help(&self) -> Option<&'static str> {
+        use AnalyseErrorType::*;
+        match self {
+            WrongNumber => Some(
+                "numbers are written like `42`, `3.14`, `1e-6`, `0xFF`, `0b1010`, `0o17` or `1_000`",
+            ),
+            UnknownFunction => Some("there is no function with this name"),
+            UnknownSymbol => Some("supported operators are `+`, `-`, `*`, `/` and `^`"),
+        }
     }
 }
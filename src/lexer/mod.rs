@@ -1,7 +1,10 @@
+use crate::context::Context;
 use crate::tokens::{Token, TokenType};
 use errors::{AnalyseError, AnalyseErrorType};
 use parsers::{parse_ident, parse_number};
 
+pub(crate) use parsers::builtin_functions;
+
 pub mod errors;
 mod parsers;
 
@@ -11,13 +14,24 @@ mod tests;
 pub fn analyse<'src>(source: &'src str) -> LexerIterator<'src> {
     LexerIterator {
         source,
+        context: None,
         pos: 0,
-        ch: source.as_bytes()[0],
+        ch: source.as_bytes().first().copied().unwrap_or(0),
+    }
+}
+
+/// Same as [`analyse`], identifiers which are not built-in are also looked up
+/// among the constants and functions of `context`
+pub fn analyse_with<'src>(source: &'src str, context: &'src Context) -> LexerIterator<'src> {
+    LexerIterator {
+        context: Some(context),
+        ..analyse(source)
     }
 }
 
 pub struct LexerIterator<'src> {
     source: &'src str,
+    context: Option<&'src Context>,
     pos: usize,
     ch: u8,
 }
@@ -38,12 +52,45 @@ impl<'src> LexerIterator<'src> {
         }
     }
 
+    fn peek_ch(&self) -> u8 {
+        self.source
+            .as_bytes()
+            .get(self.pos + 1)
+            .copied()
+            .unwrap_or(0)
+    }
+
     fn read_number(&mut self) -> &'src str {
         let pos = self.pos;
-        while self.ch.is_ascii_digit() || self.ch == b'.' {
+        if self.ch == b'0' && matches!(self.peek_ch(), b'x' | b'X' | b'b' | b'B' | b'o' | b'O') {
+            self.read_ch();
+            self.read_ch();
+            while self.ch.is_ascii_alphanumeric() || self.ch == b'_' || self.ch == b'.' {
+                self.read_ch();
+            }
+            return &self.source[pos..self.pos];
+        }
+        while self.ch.is_ascii_digit() || self.ch == b'.' || self.ch == b'_' {
             self.read_ch();
         }
-        return &self.source[pos..self.pos];
+        if matches!(self.ch, b'e' | b'E') {
+            self.read_ch();
+            if matches!(self.ch, b'+' | b'-') {
+                self.read_ch();
+            }
+            while self.ch.is_ascii_digit() || self.ch == b'.' || self.ch == b'_' {
+                self.read_ch();
+            }
+        }
+        &self.source[pos..self.pos]
+    }
+
+    fn peek_non_whitespace(&self) -> u8 {
+        let rest = &self.source.as_bytes()[self.pos.min(self.source.len())..];
+        rest.iter()
+            .copied()
+            .find(|ch| !ch.is_ascii_whitespace())
+            .unwrap_or(0)
     }
 
     fn read_ident(&mut self) -> &'src str {
@@ -51,12 +98,12 @@ impl<'src> LexerIterator<'src> {
         while self.ch.is_ascii_alphabetic() || self.ch.is_ascii_digit() || self.ch == b'_' {
             self.read_ch();
         }
-        return &self.source[pos..self.pos];
+        &self.source[pos..self.pos]
     }
 }
 
 impl<'src> Iterator for LexerIterator<'src> {
-    type Item = Result<Token<'src>, AnalyseError<'src>>;
+    type Item = Result<Token<'src>, AnalyseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         use AnalyseErrorType::*;
@@ -81,22 +128,34 @@ impl<'src> Iterator for LexerIterator<'src> {
             }
             b'A'..=b'Z' | b'a'..=b'z' | b'_' => {
                 let text = self.read_ident();
-                return Some(match parse_ident(text) {
-                    Some(tok) => Ok(Token::new(self.pos - text.len(), text, tok)),
-                    None => Err(AnalyseError::new(self.pos - text.len(), text, UnknownIdent)),
+                let pos = self.pos - text.len();
+                let token_type = match parse_ident(text) {
+                    Some(Var(_)) => self.context.and_then(|context| context.resolve(text)),
+                    token_type => token_type,
+                };
+                // names not resolved by the context are variables
+                return Some(match token_type.unwrap_or(Var(text)) {
+                    Var(_) if self.peek_non_whitespace() == b'(' => {
+                        Err(AnalyseError::new(pos, text, UnknownFunction))
+                    }
+                    tok => Ok(Token::new(pos, text, tok)),
                 });
             }
             0 => return None,
             _ => {
+                let len = self.source[self.pos..]
+                    .chars()
+                    .next()
+                    .map_or(1, char::len_utf8);
                 return Some(Err(AnalyseError::new(
                     self.pos,
-                    &self.source[self.pos..self.pos + 1],
+                    &self.source[self.pos..self.pos + len],
                     UnknownSymbol,
                 )));
             }
         };
 
         self.read_ch();
-        return Some(Ok(token));
+        Some(Ok(token))
     }
 }
@@ -1,191 +1,190 @@
 use core::f64;
 
+use crate::ast::BoundOp;
+use crate::context::{Arity, FunctionRegistry};
 use crate::tokens::TokenType;
 
-pub fn parse_number(text: &str) -> Option<TokenType> {
-    match text.parse::<f64>() {
-        Ok(n) => Some(TokenType::Number(n)),
-        Err(_) => None,
+/// Parses decimal numbers with optional exponent (`2.5E+3`) and
+/// hexadecimal, binary or octal integers (`0xFF`, `0b1010`, `0o17`).
+/// Digits may be separated with `_`
+pub fn parse_number(text: &str) -> Option<TokenType<'_>> {
+    let radix = match text.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0b" | "0B") => 2,
+        Some("0o" | "0O") => 8,
+        _ => 10,
+    };
+    let digits = if radix == 10 { text } else { &text[2..] };
+    if !valid_separators(digits, radix) {
+        return None;
     }
-}
+    let digits = digits.replace('_', "");
 
-pub fn parse_ident(text: &str) -> Option<TokenType> {
-    if let Some(token) = parse_var(text) {
-        return Some(token);
+    if radix == 10 {
+        return digits.parse::<f64>().ok().map(TokenType::Number);
     }
+    if digits.is_empty() {
+        return None;
+    }
+    digits
+        .chars()
+        .try_fold(0.0, |n, ch| {
+            Some(n * radix as f64 + ch.to_digit(radix)? as f64)
+        })
+        .map(TokenType::Number)
+}
+
+/// `_` is only allowed between two digits
+fn valid_separators(digits: &str, radix: u32) -> bool {
+    let bytes = digits.as_bytes();
+    let is_digit = |i: Option<usize>| {
+        i.and_then(|i| bytes.get(i))
+            .is_some_and(|&b| (b as char).is_digit(radix))
+    };
+    (0..bytes.len())
+        .filter(|&i| bytes[i] == b'_')
+        .all(|i| is_digit(i.checked_sub(1)) && is_digit(Some(i + 1)))
+}
+
+/// Constants and functions take priority, any other identifier is a variable
+pub fn parse_ident(text: &str) -> Option<TokenType<'_>> {
     if let Some(token) = parse_const(text) {
         return Some(token);
     }
     if let Some(token) = parse_func(text) {
         return Some(token);
     }
-    return None;
+    parse_var(text)
 }
 
-fn parse_var(text: &str) -> Option<TokenType> {
-    if text == "x" {
-        return Some(TokenType::Var);
-    }
-    return None;
+fn parse_var(text: &str) -> Option<TokenType<'_>> {
+    Some(TokenType::Var(text))
 }
 
-fn parse_const(text: &str) -> Option<TokenType> {
+fn parse_const(text: &str) -> Option<TokenType<'static>> {
     use TokenType::*;
-    return Some(match text {
+    Some(match text {
         "e" => Const(f64::consts::E),
         "pi" => Const(f64::consts::PI),
         "eps" => Const(0.000_001),
+        "inf" => Const(f64::INFINITY),
         _ => return None,
-    });
+    })
 }
 
-fn parse_func(text: &str) -> Option<TokenType> {
-    use TokenType::*;
-    return Some(match text {
-        "abs" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::abs(args[0]),
-        },
-        "acos" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::acos(args[0]),
-        },
-        "acosh" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::acosh(args[0]),
-        },
-        "asin" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::asin(args[0]),
-        },
-        "asinh" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::asinh(args[0]),
-        },
-        "atan" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::atan(args[0]),
-        },
-        "atan2" => Func {
-            args: 2,
-            func: |args: Vec<f64>| f64::atan2(args[0], args[1]),
-        },
-        "atanh" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::atanh(args[0]),
-        },
-        "cbrt" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::cbrt(args[0]),
-        },
-        "ceil" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::ceil(args[0]),
-        },
-        "clamp" => Func {
-            args: 3,
-            func: |args: Vec<f64>| f64::clamp(args[0], args[1], args[2]),
-        },
-        "cos" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::cos(args[0]),
-        },
-        "cosh" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::cosh(args[0]),
-        },
-        "exp" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::exp(args[0]),
-        },
-        "exp2" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::exp2(args[0]),
-        },
-        "floor" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::floor(args[0]),
-        },
-        "fract" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::fract(args[0]),
-        },
-        "hypot" => Func {
-            args: 2,
-            func: |args: Vec<f64>| f64::hypot(args[0], args[1]),
-        },
-        "ln" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::ln(args[0]),
-        },
-        "log2" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::log2(args[0]),
-        },
-        "log10" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::log10(args[0]),
-        },
-        "max" => Func {
-            args: 2,
-            func: |args: Vec<f64>| f64::max(args[0], args[1]),
-        },
-        "min" => Func {
-            args: 2,
-            func: |args: Vec<f64>| f64::min(args[0], args[1]),
-        },
-        "mul_add" => Func {
-            args: 3,
-            func: |args: Vec<f64>| f64::mul_add(args[0], args[1], args[2]),
-        },
-        "pow" => Func {
-            args: 2,
-            func: |args: Vec<f64>| f64::powf(args[0], args[1]),
-        },
-        "recip" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::recip(args[0]),
-        },
-        "round" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::round(args[0]),
-        },
-        "sign" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::signum(args[0]),
-        },
-        "sin" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::sin(args[0]),
-        },
-        "sinh" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::sinh(args[0]),
-        },
-        "sqrt" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::sqrt(args[0]),
-        },
-        "tan" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::tan(args[0]),
-        },
-        "tanh" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::tanh(args[0]),
-        },
-        "toDeg" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::to_degrees(args[0]),
-        },
-        "toRad" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::to_radians(args[0]),
-        },
-        "trunc" => Func {
-            args: 1,
-            func: |args: Vec<f64>| f64::trunc(args[0]),
-        },
-        _ => return None,
+/// `sum` is a function, it is told from `series` by the parser
+pub fn parse_func(text: &str) -> Option<TokenType<'static>> {
+    match FunctionRegistry::builtins().get(text) {
+        Some(function) => Some(TokenType::Func(function)),
+        None => BoundOp::from_name(text).map(TokenType::Bound),
+    }
+}
+
+/// Registry of the built-in functions, see [`FunctionRegistry::builtins`]
+pub(crate) fn builtin_functions() -> FunctionRegistry {
+    use Arity::*;
+    let mut registry = FunctionRegistry::new();
+    let mut unary = |name: &str, func: fn(f64) -> f64| {
+        registry.register(name, Fixed(1), move |args| func(args[0]));
+    };
+    unary("abs", f64::abs);
+    unary("acos", f64::acos);
+    unary("acosh", f64::acosh);
+    unary("asin", f64::asin);
+    unary("asinh", f64::asinh);
+    unary("atan", f64::atan);
+    unary("atanh", f64::atanh);
+    unary("cbrt", f64::cbrt);
+    unary("ceil", f64::ceil);
+    unary("cos", f64::cos);
+    unary("cosh", f64::cosh);
+    unary("exp", f64::exp);
+    unary("exp2", f64::exp2);
+    unary("floor", f64::floor);
+    unary("fract", f64::fract);
+    unary("ln", f64::ln);
+    unary("log2", f64::log2);
+    unary("log10", f64::log10);
+    unary("recip", f64::recip);
+    unary("round", f64::round);
+    unary("sign", f64::signum);
+    unary("sin", f64::sin);
+    unary("sinh", f64::sinh);
+    unary("sqrt", f64::sqrt);
+    unary("tan", f64::tan);
+    unary("tanh", f64::tanh);
+    unary("toDeg", f64::to_degrees);
+    unary("toRad", f64::to_radians);
+    unary("trunc", f64::trunc);
+
+    let mut binary = |name: &str, func: fn(f64, f64) -> f64| {
+        registry.register(name, Fixed(2), move |args| func(args[0], args[1]));
+    };
+    binary("atan2", f64::atan2);
+    binary("pow", f64::powf);
+
+    let mut ternary = |name: &str, func: fn(f64, f64, f64) -> f64| {
+        registry.register(name, Fixed(3), move |args| func(args[0], args[1], args[2]));
+    };
+    ternary("clamp", clamp);
+    ternary("mul_add", f64::mul_add);
+
+    let mut variadic = |name: &str, min: usize, func: fn(&[f64]) -> f64| {
+        registry.register(name, AtLeast(min), func);
+    };
+    variadic("hypot", 1, |args| {
+        args.iter().fold(0.0, |h, &a| f64::hypot(h, a))
+    });
+    variadic("max", 1, |args| {
+        args.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+    });
+    variadic("mean", 1, |args| {
+        args.iter().sum::<f64>() / args.len() as f64
+    });
+    variadic("median", 1, median);
+    variadic("min", 1, |args| {
+        args.iter().copied().fold(f64::INFINITY, f64::min)
     });
+    variadic("stdev", 2, stdev);
+    variadic("sum", 1, |args| args.iter().sum());
+    registry
+}
+
+/// `x` limited to `[min, max]`, unlike [`f64::clamp`] it does not panic
+/// for `min > max` but gives `max`, and NaN for any NaN argument
+fn clamp(x: f64, min: f64, max: f64) -> f64 {
+    match x.is_nan() || min.is_nan() || max.is_nan() {
+        true => f64::NAN,
+        false => x.max(min).min(max),
+    }
+}
+
+/// Middle value, or the mean of the two middle values, found without sorting
+/// a copy of the arguments so calls do not allocate
+fn median(args: &[f64]) -> f64 {
+    if args.iter().any(|a| a.is_nan()) {
+        return f64::NAN;
+    }
+    let mid = args.len() / 2;
+    match args.len() % 2 {
+        1 => nth_smallest(args, mid),
+        _ => 0.5 * (nth_smallest(args, mid - 1) + nth_smallest(args, mid)),
+    }
+}
+
+/// Value at index `n` of the sorted `args`, which are not NaN
+fn nth_smallest(args: &[f64], n: usize) -> f64 {
+    let count = |pred: &dyn Fn(f64) -> bool| args.iter().filter(|&&a| pred(a)).count();
+    args.iter()
+        .copied()
+        .find(|&a| count(&|b| b < a) <= n && n < count(&|b| b <= a))
+        .unwrap_or(f64::NAN)
+}
+
+/// Sample standard deviation
+fn stdev(args: &[f64]) -> f64 {
+    let n = args.len() as f64;
+    let mean = args.iter().sum::<f64>() / n;
+    let squares: f64 = args.iter().map(|a| (a - mean).powi(2)).sum();
+    (squares / (n - 1.0)).sqrt()
 }
@@ -1,6 +1,7 @@
 use core::panic;
 
 use super::*;
+use crate::context::Arity;
 use TokenType::*;
 
 #[test]
@@ -38,7 +39,7 @@ fn it_works() {
         Token {
             pos: 5,
             text: "sin",
-            token_type: Func { args: 1, .. }
+            token_type: Func(_)
         }
     ));
 
@@ -55,7 +56,7 @@ fn it_works() {
         Token {
             pos: 9,
             text: "x",
-            token_type: Var,
+            token_type: Var("x"),
         }
     );
     assert_eq!(
@@ -74,19 +75,87 @@ fn funcs_calls() {
     let mut tokens1 = analyse(text1).map(|res| res.unwrap());
     let sin_token = tokens1.next().unwrap();
     let sin_func = match sin_token.token_type {
-        Func { args: 1, func } => func,
+        Func(func) => func,
         _ => panic!("wrong function parsing"),
     };
-    let args1 = vec![0.0];
-    assert_eq!(sin_func(args1), 0.0);
+    assert_eq!(sin_func.arity(), Arity::Fixed(1));
+    assert_eq!(sin_func.call(&[0.0]), 0.0);
 
     let text2 = "max(1, 2)";
     let mut tokens2 = analyse(text2).map(|res| res.unwrap());
     let max_token = tokens2.next().unwrap();
     let max_func = match max_token.token_type {
-        Func { args: 2, func } => func,
+        Func(func) => func,
         _ => panic!("wrong function parsing"),
     };
-    let args2 = vec![1.0, 2.0];
-    assert_eq!(max_func(args2), 2.0);
+    assert_eq!(max_func.arity(), Arity::AtLeast(1));
+    assert_eq!(max_func.call(&[1.0, 2.0]), 2.0);
+}
+
+#[test]
+fn numbers() {
+    let numbers = [
+        ("42", 42.0),
+        ("3.25", 3.25),
+        (".5", 0.5),
+        ("1e-6", 0.000_001),
+        ("2.5E+3", 2500.0),
+        ("1_000_000", 1_000_000.0),
+        ("0xFF", 255.0),
+        ("0b1010", 10.0),
+        ("0o17", 15.0),
+        ("0B1_0", 2.0),
+    ];
+    for (text, expected) in numbers {
+        match analyse(text).next().unwrap() {
+            Ok(Token {
+                token_type: Number(n),
+                ..
+            }) => assert_eq!(n, expected, "{}", text),
+            token => panic!("wrong token {:?} for {}", token, text),
+        }
+    }
+}
+
+#[test]
+fn wrong_numbers() {
+    for text in [
+        "1.2.3", "1e", "1e+", "2.5e3.1", "1__0", "1_", "1_.5", "0x", "0b102", "0x_ff", "0o8",
+        "0x1.5",
+    ] {
+        let error = analyse(text).next().unwrap().unwrap_err();
+        assert_eq!(
+            error.error_type(),
+            &errors::AnalyseErrorType::WrongNumber,
+            "{}",
+            text
+        );
+        assert_eq!(error.text(), text);
+    }
+
+    let mut tokens = analyse("2*e-1").map(|res| res.unwrap());
+    assert_eq!(tokens.next().unwrap().token_type, Number(2.0));
+    assert_eq!(tokens.next().unwrap().token_type, Mul);
+    assert!(matches!(tokens.next().unwrap().token_type, Const(_)));
+}
+
+#[test]
+fn identifiers() {
+    let text = "alpha_1 * e + foo(x)";
+    let mut tokens = analyse(text);
+
+    assert_eq!(tokens.next().unwrap().unwrap().token_type, Var("alpha_1"));
+    assert_eq!(tokens.next().unwrap().unwrap().token_type, Mul);
+    assert!(matches!(
+        tokens.next().unwrap().unwrap().token_type,
+        Const(_)
+    ));
+    assert_eq!(tokens.next().unwrap().unwrap().token_type, Add);
+
+    let error = tokens.next().unwrap().unwrap_err();
+    assert_eq!(
+        error.error_type(),
+        &errors::AnalyseErrorType::UnknownFunction
+    );
+    assert_eq!(error.text(), "foo");
 }
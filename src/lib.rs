@@ -0,0 +1,19 @@
+pub mod ast;
+pub mod colors;
+pub mod context;
+pub mod diff;
+pub mod executor;
+mod expression;
+pub mod jit;
+pub mod lexer;
+pub mod optimizer;
+pub mod parser;
+pub mod solver_error;
+pub mod solvers;
+pub mod tokens;
+pub mod vm;
+
+pub use context::Context;
+pub use executor::{Complex, Env};
+pub use expression::Expression;
+pub use solver_error::Error;
@@ -1,21 +1,43 @@
-use clap::{Parser, Subcommand};
-use solver_error::SolverError;
-use solvers::{integral, root};
-use std::{self, process::exit};
-
-pub mod colors;
-mod executor;
-mod lexer;
-mod parser;
-pub mod solver_error;
-mod solvers;
-pub mod tokens;
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{Args, Parser, Subcommand};
+use solver::executor::errors::ExecutionErrorType;
+use solver::solver_error::SolverError;
+use solver::solvers::{
+    derivative, integral, muller, newton, root, roots, IntegralMethod, RootMethod,
+};
+use solver::tokens::TokenType;
+use solver::{lexer, Complex, Context, Env, Error, Expression};
+use std::path::PathBuf;
+use std::process::exit;
+
+mod repl;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Variable binding, e.g. `--var a=pi/3`. Can be repeated, later
+    /// bindings may use earlier ones (expression allowed)
+    #[arg(long = "var", value_name = "NAME=EXPR", global = true)]
+    vars: Vec<String>,
+    /// Constant or function definition, e.g. `--define "g = 9.80665"` or
+    /// `--define "sigmoid(x) = 1/(1 + exp(-x))"`. Can be repeated, later
+    /// definitions may use earlier ones
+    #[arg(long = "define", value_name = "DEFINITION", global = true)]
+    defines: Vec<String>,
+    /// File with a definition per line, empty lines and lines starting with
+    /// `#` are skipped. Loaded before `--define` definitions
+    #[arg(long = "load", value_name = "FILE", global = true)]
+    loads: Vec<PathBuf>,
+    /// Print solver report: residual of roots, error estimate, iteration and
+    /// evaluation counts, final bracket or step and termination reason
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// Compute with complex numbers: `i` is the imaginary unit and
+    /// `sqrt(-1)` is not NaN. Supported by `eval` and `root`
+    #[arg(long, global = true)]
+    complex: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -25,146 +47,540 @@ enum Commands {
     Eval {
         /// Expression to be evaluated
         expr: String,
-        /// Argument `x` value. Not required for expressions without
-        /// arguments (expression allowed)
+        /// Argument `x` value, same as `--var x=<X>` (expression allowed)
         #[arg(short)]
         x: Option<String>,
+        /// Also print derivatives up to order <N>, one per line after the
+        /// value, computed with automatic differentiation. Defaults to 1
+        #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "1")]
+        derivative: Option<usize>,
+        /// Variable to differentiate with respect to. Defaults to `x`
+        #[arg(long, default_value = "x")]
+        wrt: String,
+        /// Print the value in polar form `r∠theta` with the angle in radians,
+        /// implies `--complex`
+        #[arg(long, conflicts_with = "derivative")]
+        polar: bool,
     },
-    /// Find root of the <EXPR> with variable `x` with `false position`
-    /// root-finding algorithm on the interval between <X1> and <X2>
+    /// Print derivative of the <EXPR>
     #[command(arg_required_else_help = true)]
-    Root {
-        /// Expression with variable `x`
+    Diff {
+        /// Expression to be differentiated
         expr: String,
-        /// Value defining interval for root (expression allowed)
-        x1: String,
-        /// Same as for <X1>
-        x2: String,
-        /// Root eps. Defaults to 1e-6 (expression allowed)
-        #[arg(long)]
-        eps: Option<String>,
-        /// Max iterations allowed for root evaluation. Defaults to 100,000
-        #[arg(long)]
-        max_iterations: Option<usize>,
+        /// Variable to differentiate with respect to. Defaults to `x`
+        #[arg(long, default_value = "x")]
+        wrt: String,
     },
-    /// Find definite integral of the <EXPR> with variable `x` with iterative
-    /// `rectangular` method on the interval between <X1> and <X2>
+    /// Print the <EXPR> with constant subexpressions folded and identities
+    /// like `x*1` removed
+    Simplify {
+        /// Expression to be simplified
+        expr: String,
+    },
+    /// Print values of the <EXPR> with variable `x` at <STEPS> equal steps
+    /// between <X1> and <X2>, a row `x value` per point
     #[command(arg_required_else_help = true)]
-    Integral {
+    Table {
         /// Expression with variable `x`
         expr: String,
-        /// Value defining interval for integral (expression allowed)
+        /// First value of `x` (expression allowed)
         x1: String,
-        /// Same as for <X1>
+        /// Last value of `x` (expression allowed)
         x2: String,
-        /// Integral eps. Defaults to 1e-6 (expression allowed)
-        #[arg(long)]
-        eps: Option<String>,
-        /// Max iterations allowed for integral evaluation. Defaults to 100,000
+        /// Number of steps between <X1> and <X2>. Defaults to 10
+        #[arg(long, default_value_t = 10)]
+        steps: usize,
+        /// Variable to tabulate. Defaults to `x`
+        #[arg(long, default_value = "x")]
+        wrt: String,
+        /// Number of threads evaluating the rows. Defaults to 1
+        #[arg(long, default_value_t = 1)]
+        threads: usize,
+    },
+    /// Start interactive session with line editing and history. Variables
+    /// assigned with `name = <EXPR>` persist, `ans` holds the last result
+    Repl,
+    /// Find derivative of the <EXPR> with variable `x` at <X0> numerically
+    #[command(arg_required_else_help = true)]
+    Derivative {
+        #[command(flatten)]
+        args: DerivativeArgs,
+    },
+    /// Find root of the <EXPR> with variable `x` on the interval between <X1>
+    /// and <X2>
+    #[command(arg_required_else_help = true)]
+    Root {
+        #[command(flatten)]
+        args: SolveArgs,
+        /// Root-finding algorithm. Bracketing methods need a sign change
+        /// between <X1> and <X2>, `secant` starts from <X1> and <X2> and
+        /// `newton` from their middle using the symbolic derivative.
+        /// Defaults to `brent`
+        #[arg(long, value_parser = root_method_parser())]
+        method: Option<RootMethod>,
+    },
+    /// Find all roots of the <EXPR> with variable `x` on the interval between
+    /// <X1> and <X2>
+    #[command(arg_required_else_help = true)]
+    Roots {
+        #[command(flatten)]
+        args: SolveArgs,
+        /// Number of equal steps the interval is sampled at, roots closer
+        /// than a step may be missed. Defaults to 1,000
         #[arg(long)]
-        max_iterations: Option<usize>,
+        samples: Option<usize>,
+    },
+    /// Find definite integral of the <EXPR> with variable `x` on the interval
+    /// between <X1> and <X2>
+    #[command(arg_required_else_help = true)]
+    Integral {
+        #[command(flatten)]
+        args: SolveArgs,
+        /// Quadrature rule. Adaptive `simpson` and `gauss-kronrod` refine the
+        /// subintervals with the largest error, `tanh-sinh` copes with
+        /// singularities at the limits. Defaults to `gauss-kronrod`
+        #[arg(long, value_parser = integral_method_parser())]
+        method: Option<IntegralMethod>,
     },
 }
 
+/// Arguments shared by the solvers working on an interval
+#[derive(Debug, Args)]
+struct SolveArgs {
+    /// Expression with variable `x`
+    expr: String,
+    /// Value defining the interval (expression allowed)
+    x1: String,
+    /// Same as for <X1>
+    x2: String,
+    /// Variable to solve for. Defaults to `x`
+    #[arg(long, default_value = "x")]
+    wrt: String,
+    /// Solution eps. Defaults to 1e-6 (expression allowed)
+    #[arg(long)]
+    eps: Option<String>,
+    /// Max iterations allowed for evaluation. Defaults to 100,000
+    #[arg(long)]
+    max_iterations: Option<usize>,
+}
+
+/// Arguments of the numerical derivative
+#[derive(Debug, Args)]
+struct DerivativeArgs {
+    /// Expression with variable `x`
+    expr: String,
+    /// Point to differentiate at (expression allowed)
+    x0: String,
+    /// Order of the derivative
+    #[arg(long, default_value_t = 1)]
+    order: usize,
+    /// Variable to differentiate with respect to. Defaults to `x`
+    #[arg(long, default_value = "x")]
+    wrt: String,
+    /// Solution eps. Defaults to 1e-6 (expression allowed)
+    #[arg(long)]
+    eps: Option<String>,
+    /// Max number of steps tried by the extrapolation. Defaults to 10
+    #[arg(long)]
+    max_iterations: Option<usize>,
+}
+
 fn main() {
     let args = Cli::parse();
+    let context = get_context(&args.loads, &args.defines);
+    if args.complex || matches!(args.command, Commands::Eval { polar: true, .. }) {
+        let env = get_complex_env(&args.vars, &context);
+        return run_complex(args.command, args.verbose, env, &context);
+    }
+    let mut env = get_env(&args.vars, &context);
 
     match args.command {
-        Commands::Eval { expr, x } => run_eval(expr, x),
-        Commands::Root {
+        Commands::Eval {
             expr,
-            x1,
-            x2,
-            eps,
-            max_iterations,
-        } => run_root(expr, x1, x2, eps, max_iterations),
-        Commands::Integral {
+            x,
+            derivative,
+            wrt,
+            ..
+        } => {
+            if let Some(x_expr) = x {
+                env.set("x", eval_expr(&x_expr, &env, &context));
+            }
+            match derivative {
+                Some(order) => run_eval_derivative(expr, wrt, order, env, &context),
+                None => run_eval(expr, env, &context),
+            }
+        }
+        Commands::Diff { expr, wrt } => run_diff(expr, wrt, &context),
+        Commands::Simplify { expr } => run_simplify(expr, &context),
+        Commands::Table {
             expr,
             x1,
             x2,
-            eps,
-            max_iterations,
-        } => run_integral(expr, x1, x2, eps, max_iterations),
+            steps,
+            wrt,
+            threads,
+        } => run_table(expr, (x1, x2), steps, wrt, threads, env, &context),
+        Commands::Repl => repl::run(env, context),
+        Commands::Derivative { args: solve } => run_derivative(solve, args.verbose, env, &context),
+        Commands::Root {
+            args: solve,
+            method,
+        } => run_root(
+            solve,
+            method.unwrap_or_default(),
+            args.verbose,
+            env,
+            &context,
+        ),
+        Commands::Roots {
+            args: solve,
+            samples,
+        } => run_roots(solve, samples, args.verbose, env, &context),
+        Commands::Integral {
+            args: solve,
+            method,
+        } => run_integral(
+            solve,
+            method.unwrap_or_default(),
+            args.verbose,
+            env,
+            &context,
+        ),
     }
 }
 
-fn get_expr_closure(expr: &str) -> impl Fn(f64) -> f64 + '_ {
-    let tokens_it =
-        lexer::analyse(&expr).map(|res| res.map_err(|e| print_error(e, &expr)).unwrap());
-    let postfix_tokens = parser::parse(tokens_it)
-        .map_err(|e| print_error(e, &expr))
-        .unwrap();
-    move |x| {
-        executor::eval(&postfix_tokens, Some(x))
-            .map_err(|e| print_error(e, &expr))
-            .unwrap()
+fn root_method_parser() -> impl TypedValueParser<Value = RootMethod> {
+    PossibleValuesParser::new(RootMethod::ALL.map(|method| method.name()))
+        .map(|name| name.parse::<RootMethod>().unwrap())
+}
+
+fn integral_method_parser() -> impl TypedValueParser<Value = IntegralMethod> {
+    PossibleValuesParser::new(IntegralMethod::ALL.map(|method| method.name()))
+        .map(|name| name.parse::<IntegralMethod>().unwrap())
+}
+
+fn print_error(error: impl Into<Error>, source: &str) -> ! {
+    // variables are bound with `--var` on the command line
+    let error = match error.into() {
+        Error::Execution(e) if e.error_type() == &ExecutionErrorType::UnknownIdent => {
+            let help = format!("bind it with `--var {}=<value>`", e.text());
+            Error::Execution(e.with_help(help))
+        }
+        error => error,
+    };
+    eprintln!("{}", error.display_solver_error(source));
+    exit(1);
+}
+
+fn parse_expr(expr: &str, context: &Context) -> Expression {
+    Expression::parse_with(expr, context).unwrap_or_else(|e| print_error(e, expr))
+}
+
+fn eval_expr(expr: &str, env: &Env, context: &Context) -> f64 {
+    parse_expr(expr, context)
+        .eval(env)
+        .unwrap_or_else(|e| print_error(e, expr))
+}
+
+/// Reads definitions from `--load` files, then `--define` ones
+fn get_context(loads: &[PathBuf], defines: &[String]) -> Context {
+    let mut context = Context::new();
+    for path in loads {
+        let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("error: can not read `{}`: {}", path.display(), e);
+            exit(1);
+        });
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Err(e) = context.define(line) {
+                eprintln!("in {}:{}", path.display(), n + 1);
+                print_error(e, line);
+            }
+        }
+    }
+    for definition in defines {
+        context
+            .define(definition)
+            .unwrap_or_else(|e| print_error(e, definition));
+    }
+    context
+}
+
+/// Evaluates `--var NAME=EXPR` bindings in order
+fn get_env(vars: &[String], context: &Context) -> Env {
+    let mut env = Env::new();
+    for var in vars {
+        let Some((name, expr)) = var.split_once('=') else {
+            eprintln!("error: variable binding `{}` must look like NAME=EXPR", var);
+            exit(1);
+        };
+        let name = name.trim();
+        if !is_var_name(name, context) {
+            eprintln!("error: `{}` can not be used as variable name", name);
+            exit(1);
+        }
+        env.set(name, eval_expr(expr, &env, context));
+    }
+    env
+}
+
+/// Evaluates `--var NAME=EXPR` bindings in order on complex numbers
+fn get_complex_env(vars: &[String], context: &Context) -> Env<Complex> {
+    let mut env = Env::new();
+    for var in vars {
+        let Some((name, expr)) = var.split_once('=') else {
+            eprintln!("error: variable binding `{}` must look like NAME=EXPR", var);
+            exit(1);
+        };
+        let name = name.trim();
+        if !is_var_name(name, context) || name == "i" {
+            eprintln!("error: `{}` can not be used as variable name", name);
+            exit(1);
+        }
+        env.set(name, eval_complex_expr(expr, &env, context));
+    }
+    env
+}
+
+/// Whether `name` is an identifier which is not a constant or a function
+fn is_var_name(name: &str, context: &Context) -> bool {
+    let mut tokens = lexer::analyse_with(name, context);
+    matches!(
+        (tokens.next(), tokens.next()),
+        (Some(Ok(token)), None) if matches!(token.token_type, TokenType::Var(_))
+    )
+}
+
+fn eval_complex_expr(expr: &str, env: &Env<Complex>, context: &Context) -> Complex {
+    parse_expr(expr, context)
+        .eval_complex(env)
+        .unwrap_or_else(|e| print_error(e, expr))
+}
+
+/// Runs the commands supporting `--complex`
+fn run_complex(command: Commands, verbose: bool, mut env: Env<Complex>, context: &Context) {
+    match command {
+        Commands::Eval {
+            expr,
+            x,
+            derivative: None,
+            polar,
+            ..
+        } => {
+            if let Some(x_expr) = x {
+                env.set("x", eval_complex_expr(&x_expr, &env, context));
+            }
+            let value = eval_complex_expr(&expr, &env, context);
+            match polar {
+                true => println!("{}", value.polar()),
+                false => println!("{}", value),
+            }
+        }
+        Commands::Root { args, method: None } => run_complex_root(args, verbose, env, context),
+        _ => {
+            eprintln!("error: `--complex` is supported by `eval` and `root` without `--method` or `--derivative`");
+            exit(1);
+        }
     }
 }
 
-fn eval_expr(expr: String, x: Option<String>) -> f64 {
-    let unwrap_solver = get_solver_unwrapper(&expr);
-    let x_value = match x {
-        Some(x_expr) => Some(eval_expr(x_expr, None)),
-        None => None,
+/// Finds a complex root with Muller's method, which starts from <X1>, <X2>
+/// and their midpoint
+fn run_complex_root(args: SolveArgs, verbose: bool, env: Env<Complex>, context: &Context) {
+    const ROOT_EPS: f64 = 0.000_001;
+    let x1 = eval_complex_expr(&args.x1, &env, context);
+    let x2 = eval_complex_expr(&args.x2, &env, context);
+    let eps = match args.eps {
+        Some(eps_expr) => eval_complex_expr(&eps_expr, &env, context).re,
+        None => ROOT_EPS,
     };
-    let tokens_it = lexer::analyse(&expr).map(unwrap_solver);
-    let postfix_tokens = parser::parse(tokens_it)
-        .map_err(|e| print_error(e, &expr))
-        .unwrap();
-    return executor::eval(&postfix_tokens, x_value)
-        .map_err(|e| print_error(e, &expr))
-        .unwrap();
+    let expression = parse_expr(&args.expr, context);
+    let f = expression
+        .complex_closure(&args.wrt, &env)
+        .unwrap_or_else(|e| print_error(e, &args.expr));
+    let report = muller(f, x1, x2, eps, args.max_iterations);
+    if verbose {
+        println!("{}", report);
+        return;
+    }
+    match report.ok() {
+        Some(z) => println!("{}", z),
+        None => println!("could not find root: {}", report.termination),
+    }
 }
 
-fn run_eval(expr: String, x: Option<String>) {
-    println!("{}", eval_expr(expr, x));
+fn run_eval(expr: String, env: Env, context: &Context) {
+    println!("{}", eval_expr(&expr, &env, context));
 }
 
-fn print_error(error: impl SolverError, source: &str) {
-    eprintln!("{}", error.display_solver_error(source));
-    exit(1);
+fn run_eval_derivative(expr: String, wrt: String, order: usize, env: Env, context: &Context) {
+    let Some(x) = env.get(&wrt) else {
+        eprintln!(
+            "error: bind `{0}` to differentiate at it, e.g. `--var {0}=1`",
+            wrt
+        );
+        exit(1);
+    };
+    let taylor = parse_expr(&expr, context)
+        .taylor(&wrt, x, order, &env)
+        .unwrap_or_else(|e| print_error(e, &expr));
+    for value in taylor.derivatives() {
+        println!("{}", value);
+    }
 }
 
-fn get_solver_unwrapper<T, E: SolverError>(expr: &str) -> impl Fn(Result<T, E>) -> T + '_ {
-    |result| result.map_err(|error| print_error(error, expr)).unwrap()
+fn run_diff(expr: String, wrt: String, context: &Context) {
+    let derivative = parse_expr(&expr, context)
+        .derivative(&wrt)
+        .unwrap_or_else(|e| print_error(e, &expr));
+    println!("{}", derivative);
 }
 
-fn run_root(
+fn run_simplify(expr: String, context: &Context) {
+    println!("{}", parse_expr(&expr, context).simplify());
+}
+
+fn run_table(
     expr: String,
-    x1: String,
-    x2: String,
-    eps: Option<String>,
-    max_iterations: Option<usize>,
+    (x1, x2): (String, String),
+    steps: usize,
+    wrt: String,
+    threads: usize,
+    env: Env,
+    context: &Context,
 ) {
+    let x1 = eval_expr(&x1, &env, context);
+    let x2 = eval_expr(&x2, &env, context);
+    let steps = steps.max(1);
+    let xs: Vec<f64> = (0..=steps)
+        .map(|i| x1 + (x2 - x1) * i as f64 / steps as f64)
+        .collect();
+    let values = parse_expr(&expr, context)
+        .eval_batch_parallel(&[(&wrt, &xs)], &env, threads)
+        .unwrap_or_else(|e| print_error(e, &expr));
+    for (x, value) in xs.iter().zip(values) {
+        println!("{}\t{}", x, value);
+    }
+}
+
+fn run_derivative(args: DerivativeArgs, verbose: bool, env: Env, context: &Context) {
+    const DERIVATIVE_EPS: f64 = 0.000_001;
+    let x0 = eval_expr(&args.x0, &env, context);
+    let eps = match args.eps {
+        Some(eps_expr) => eval_expr(&eps_expr, &env, context),
+        None => DERIVATIVE_EPS,
+    };
+    let expression = parse_expr(&args.expr, context);
+    let f = expression
+        .closure(&args.wrt, &env)
+        .unwrap_or_else(|e| print_error(e, &args.expr));
+    let report = derivative(f, x0, args.order, eps, args.max_iterations);
+    if verbose {
+        println!("{}", report);
+        return;
+    }
+    if !report.is_converged() {
+        eprintln!(
+            "warning: derivative did not converge: {}",
+            report.termination
+        );
+    }
+    println!("{}", report.value);
+}
+
+fn run_root(args: SolveArgs, method: RootMethod, verbose: bool, env: Env, context: &Context) {
     const ROOT_EPS: f64 = 0.000_001;
-    let x1 = eval_expr(x1, None);
-    let x2 = eval_expr(x2, None);
-    let eps = match eps {
-        Some(eps_expr) => eval_expr(eps_expr, None),
+    let x1 = eval_expr(&args.x1, &env, context);
+    let x2 = eval_expr(&args.x2, &env, context);
+    let eps = match args.eps {
+        Some(eps_expr) => eval_expr(&eps_expr, &env, context),
         None => ROOT_EPS,
     };
-    match root(get_expr_closure(&expr), x1, x2, eps, max_iterations) {
+    let expression = parse_expr(&args.expr, context);
+    let f = expression
+        .closure(&args.wrt, &env)
+        .unwrap_or_else(|e| print_error(e, &args.expr));
+    // Newton's method gets the exact derivative when there is one
+    let derivative = match method {
+        RootMethod::Newton => expression.derivative(&args.wrt).ok(),
+        _ => None,
+    };
+    let report = match &derivative {
+        Some(derivative) => {
+            let df = derivative
+                .closure(&args.wrt, &env)
+                .unwrap_or_else(|e| print_error(e, &args.expr));
+            newton(f, df, 0.5 * (x1 + x2), eps, args.max_iterations)
+        }
+        None => root(f, x1, x2, eps, args.max_iterations, method),
+    };
+    if verbose {
+        println!("{}", report);
+        return;
+    }
+    match report.ok() {
         Some(n) => println!("{}", n),
-        None => println!("could not find root"),
+        None => println!("could not find root: {}", report.termination),
+    }
+}
+
+fn run_roots(args: SolveArgs, samples: Option<usize>, verbose: bool, env: Env, context: &Context) {
+    const ROOTS_EPS: f64 = 0.000_001;
+    const ROOTS_SAMPLES: usize = 1_000;
+    let x1 = eval_expr(&args.x1, &env, context);
+    let x2 = eval_expr(&args.x2, &env, context);
+    let eps = match args.eps {
+        Some(eps_expr) => eval_expr(&eps_expr, &env, context),
+        None => ROOTS_EPS,
+    };
+    let expression = parse_expr(&args.expr, context);
+    let f = expression
+        .closure(&args.wrt, &env)
+        .unwrap_or_else(|e| print_error(e, &args.expr));
+    let samples = samples.unwrap_or(ROOTS_SAMPLES);
+    let found = roots(f, x1, x2, eps, samples, args.max_iterations);
+    if found.is_empty() {
+        println!("could not find roots");
+    }
+    for found_root in found {
+        match verbose {
+            true => println!(
+                "{} ({}, residual {:e})",
+                found_root.value, found_root.multiplicity, found_root.residual
+            ),
+            false => println!("{} ({})", found_root.value, found_root.multiplicity),
+        }
     }
 }
 
 fn run_integral(
-    expr: String,
-    x1: String,
-    x2: String,
-    eps: Option<String>,
-    max_iterations: Option<usize>,
+    args: SolveArgs,
+    method: IntegralMethod,
+    verbose: bool,
+    env: Env,
+    context: &Context,
 ) {
     const INTEGRAL_EPS: f64 = 0.000_001;
-    let x1 = eval_expr(x1, None);
-    let x2 = eval_expr(x2, None);
-    let eps = match eps {
-        Some(eps_expr) => eval_expr(eps_expr, None),
+    let x1 = eval_expr(&args.x1, &env, context);
+    let x2 = eval_expr(&args.x2, &env, context);
+    let eps = match args.eps {
+        Some(eps_expr) => eval_expr(&eps_expr, &env, context),
         None => INTEGRAL_EPS,
     };
-    println!(
-        "{}",
-        integral(get_expr_closure(&expr), x1, x2, eps, max_iterations)
-    );
+    let expression = parse_expr(&args.expr, context);
+    let f = expression
+        .closure(&args.wrt, &env)
+        .unwrap_or_else(|e| print_error(e, &args.expr));
+    let report = integral(f, x1, x2, eps, args.max_iterations, method);
+    if verbose {
+        println!("{}", report);
+        return;
+    }
+    if !report.is_converged() {
+        eprintln!("warning: integral did not converge: {}", report.termination);
+    }
+    println!("{}", report.value);
 }
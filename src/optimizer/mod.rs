@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::ast::{BinaryOp, Node, NodeKind, UnaryOp};
+use crate::context::{FunctionRegistry, NativeFunction};
+use crate::executor;
+use crate::tokens::Span;
+
+#[cfg(test)]
+mod tests;
+
+/// Folds constant subexpressions and shares the common ones, see [`fold`] and
+/// [`share_common`]. The result evaluates to the same values as `node`
+pub fn optimize(node: Node) -> Node {
+    share_common(fold(node))
+}
+
+/// Replaces subexpressions without variables like `pi/3` or `sqrt(2)` with
+/// their values and removes identities holding for any value: `x*1`, `x/1`,
+/// `x + 0`, `x - 0`, `x^1` and double negation. Operands are never dropped,
+/// so errors evaluating them are kept. Lone constants are kept, and functions
+/// registered in a context are never called as they may have state
+pub fn fold(node: Node) -> Node {
+    let Node { kind, span } = node;
+    let fold_box = |node: Box<Node>| Box::new(fold(*node));
+    let kind = match kind {
+        NodeKind::Unary {
+            op: UnaryOp::Neg,
+            operand,
+        } => match fold(*operand) {
+            Node {
+                kind:
+                    NodeKind::Unary {
+                        op: UnaryOp::Neg,
+                        operand,
+                    },
+                ..
+            } => return *operand,
+            operand => NodeKind::Unary {
+                op: UnaryOp::Neg,
+                operand: Box::new(operand),
+            },
+        },
+        NodeKind::Binary { op, lhs, rhs } => return binary(op, fold(*lhs), fold(*rhs), span),
+        NodeKind::Call { name, func, args } => NodeKind::Call {
+            name,
+            func,
+            args: args.into_iter().map(fold).collect(),
+        },
+        NodeKind::Bound {
+            op,
+            var,
+            from,
+            to,
+            body,
+        } => NodeKind::Bound {
+            op,
+            var,
+            from: fold_box(from),
+            to: fold_box(to),
+            body: fold_box(body),
+        },
+        NodeKind::Let { var, value, body } => NodeKind::Let {
+            var,
+            value: fold_box(value),
+            body: fold_box(body),
+        },
+        kind => kind,
+    };
+    evaluate(Node::new(kind, span))
+}
+
+/// Replaces `node` with its value if its operands are constant
+fn evaluate(node: Node) -> Node {
+    if !is_constant(&node) {
+        return node;
+    }
+    // bound operators can still fail, those are left to report the error
+    match executor::eval(&node, &|_| None) {
+        Ok(value) => Node::new(NodeKind::Number(value), node.span),
+        Err(_) => node,
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Node, rhs: Node, span: Span) -> Node {
+    use BinaryOp::*;
+    let number = |node: &Node| match node.kind {
+        NodeKind::Number(n) => Some(n),
+        _ => None,
+    };
+    match (op, number(&lhs), number(&rhs)) {
+        (Mul, Some(1.0), _) | (Add, Some(0.0), _) => rhs,
+        (Mul | Div | Pow, _, Some(1.0)) | (Add | Sub, _, Some(0.0)) => lhs,
+        _ => {
+            let kind = NodeKind::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+            evaluate(Node::new(kind, span))
+        }
+    }
+}
+
+/// Whether the operands of `node` are numbers or constants, so it can be
+/// replaced with its value
+fn is_constant(node: &Node) -> bool {
+    let is_value = |node: &Node| matches!(node.kind, NodeKind::Number(_) | NodeKind::Const { .. });
+    match &node.kind {
+        NodeKind::Unary { operand, .. } => is_value(operand),
+        NodeKind::Binary { lhs, rhs, .. } => is_value(lhs) && is_value(rhs),
+        NodeKind::Call { name, func, args } => is_builtin(name, func) && args.iter().all(is_value),
+        NodeKind::Bound { from, to, body, .. } => {
+            is_value(from) && is_value(to) && calls_builtins_only(body)
+        }
+        _ => false,
+    }
+}
+
+pub(crate) fn is_builtin(name: &str, func: &Arc<NativeFunction>) -> bool {
+    FunctionRegistry::builtins()
+        .get(name)
+        .is_some_and(|builtin| Arc::ptr_eq(builtin, func))
+}
+
+fn calls_builtins_only(node: &Node) -> bool {
+    match &node.kind {
+        NodeKind::Number(_) | NodeKind::Const { .. } | NodeKind::Var(_) => true,
+        NodeKind::Unary { operand, .. } => calls_builtins_only(operand),
+        NodeKind::Binary { lhs, rhs, .. } => calls_builtins_only(lhs) && calls_builtins_only(rhs),
+        NodeKind::Call { name, func, args } => {
+            is_builtin(name, func) && args.iter().all(calls_builtins_only)
+        }
+        NodeKind::Bound { from, to, body, .. } => {
+            calls_builtins_only(from) && calls_builtins_only(to) && calls_builtins_only(body)
+        }
+        NodeKind::Let { value, body, .. } => {
+            calls_builtins_only(value) && calls_builtins_only(body)
+        }
+    }
+}
+
+/// Computes subexpressions occurring more than once only once, binding them
+/// with [`NodeKind::Let`] to variables named `$0`, `$1`, ... The bindings are
+/// made at the root and at the root of every body of a bound operator, as the
+/// subexpressions there may depend on the bound variable
+pub fn share_common(node: Node) -> Node {
+    share_scope(node, &mut 0)
+}
+
+fn share_scope(node: Node, next: &mut usize) -> Node {
+    let mut counts = HashMap::new();
+    count(&node, &mut counts);
+    let span = node.span;
+    let mut sharing = Sharing {
+        counts,
+        names: HashMap::new(),
+        lets: Vec::new(),
+        next,
+    };
+    let root = sharing.replace(node);
+    sharing
+        .lets
+        .into_iter()
+        .rev()
+        .fold(root, |body, (var, value)| {
+            let kind = NodeKind::Let {
+                var,
+                value: Box::new(value),
+                body: Box::new(body),
+            };
+            Node::new(kind, span)
+        })
+}
+
+/// Subexpressions which are cheaper to compute again than to look up
+fn is_trivial(node: &Node) -> bool {
+    match &node.kind {
+        NodeKind::Number(_) | NodeKind::Const { .. } | NodeKind::Var(_) => true,
+        NodeKind::Unary { operand, .. } => is_trivial(operand),
+        _ => false,
+    }
+}
+
+/// Counts occurrences of the subexpressions by their text, which tells the
+/// structure apart
+fn count(node: &Node, counts: &mut HashMap<String, usize>) {
+    count_unshared(node, counts, &HashMap::new());
+}
+
+/// Same as [`count`] without the subexpressions in `shared` and inside them
+fn count_unshared(
+    node: &Node,
+    counts: &mut HashMap<String, usize>,
+    shared: &HashMap<String, String>,
+) {
+    if !is_trivial(node) {
+        let key = node.to_string();
+        if shared.contains_key(&key) {
+            return;
+        }
+        *counts.entry(key).or_default() += 1;
+    }
+    let mut count = |node: &Node| count_unshared(node, counts, shared);
+    match &node.kind {
+        NodeKind::Unary { operand, .. } => count(operand),
+        NodeKind::Binary { lhs, rhs, .. } => {
+            count(lhs);
+            count(rhs);
+        }
+        NodeKind::Call { args, .. } => args.iter().for_each(count),
+        NodeKind::Bound { from, to, .. } => {
+            count(from);
+            count(to);
+        }
+        NodeKind::Let { value, body, .. } => {
+            count(value);
+            count(body);
+        }
+        _ => {}
+    }
+}
+
+struct Sharing<'a> {
+    counts: HashMap<String, usize>,
+    /// Variables of the shared subexpressions by their text
+    names: HashMap<String, String>,
+    /// Shared subexpressions, each one only uses the ones before it
+    lets: Vec<(String, Node)>,
+    next: &'a mut usize,
+}
+
+impl Sharing<'_> {
+    fn replace(&mut self, node: Node) -> Node {
+        if is_trivial(&node) {
+            return node;
+        }
+        let key = node.to_string();
+        let uses = self.counts.get(&key).copied().unwrap_or(0);
+        if uses < 2 {
+            return self.replace_operands(node);
+        }
+        let span = node.span;
+        let var = match self.names.get(&key) {
+            Some(var) => var.clone(),
+            None => {
+                self.discount_operands(&node, &key, uses);
+                let value = self.replace_operands(node);
+                let var = format!("${}", self.next);
+                *self.next += 1;
+                self.names.insert(key, var.clone());
+                self.lets.push((var.clone(), value));
+                var
+            }
+        };
+        Node::new(NodeKind::Var(var), span)
+    }
+
+    /// Takes the occurrences in `node` shared under `key` off all but one of
+    /// its `uses`. Those inside subexpressions shared before were already
+    /// taken off
+    fn discount_operands(&mut self, node: &Node, key: &str, uses: usize) {
+        let mut inner = HashMap::new();
+        count_unshared(node, &mut inner, &self.names);
+        inner.remove(key);
+        for (key, n) in inner {
+            if let Some(total) = self.counts.get_mut(&key) {
+                *total -= n * (uses - 1);
+            }
+        }
+    }
+
+    fn replace_operands(&mut self, node: Node) -> Node {
+        let Node { kind, span } = node;
+        let kind = match kind {
+            NodeKind::Unary { op, operand } => NodeKind::Unary {
+                op,
+                operand: Box::new(self.replace(*operand)),
+            },
+            NodeKind::Binary { op, lhs, rhs } => NodeKind::Binary {
+                op,
+                lhs: Box::new(self.replace(*lhs)),
+                rhs: Box::new(self.replace(*rhs)),
+            },
+            NodeKind::Call { name, func, args } => NodeKind::Call {
+                name,
+                func,
+                args: args.into_iter().map(|arg| self.replace(arg)).collect(),
+            },
+            NodeKind::Bound {
+                op,
+                var,
+                from,
+                to,
+                body,
+            } => NodeKind::Bound {
+                op,
+                var,
+                from: Box::new(self.replace(*from)),
+                to: Box::new(self.replace(*to)),
+                body: Box::new(share_scope(*body, self.next)),
+            },
+            NodeKind::Let { var, value, body } => NodeKind::Let {
+                var,
+                value: Box::new(self.replace(*value)),
+                body: Box::new(self.replace(*body)),
+            },
+            kind => kind,
+        };
+        Node::new(kind, span)
+    }
+}
@@ -0,0 +1,108 @@
+use super::*;
+use crate::context::Arity;
+use crate::solver_error::{Error, SolverError};
+use crate::{Context, Env, Expression};
+
+fn folded(text: &str) -> String {
+    fold(Expression::parse(text).unwrap().ast().clone()).to_string()
+}
+
+/// Number of subexpressions bound with `Let`
+fn shared(node: &Node) -> usize {
+    match &node.kind {
+        NodeKind::Let { value, body, .. } => 1 + shared(value) + shared(body),
+        NodeKind::Unary { operand, .. } => shared(operand),
+        NodeKind::Binary { lhs, rhs, .. } => shared(lhs) + shared(rhs),
+        NodeKind::Call { args, .. } => args.iter().map(shared).sum(),
+        NodeKind::Bound { from, to, body, .. } => shared(from) + shared(to) + shared(body),
+        _ => 0,
+    }
+}
+
+#[test]
+fn folding() {
+    for (text, expected) in [
+        ("pi/3*x + sqrt(4)", "1.0471975511965976*x + 2"),
+        ("2^10 - max(1, 2, 3)", "1021"),
+        ("x*(3 - 2) + (y - y)", "x + (y - y)"),
+        ("(x + 0)^1/1 - 0", "x"),
+        ("1*x + 0*x", "x + 0*x"),
+        ("--x + -(-(-y))", "x + -y"),
+        ("sin(x)^0", "sin(x)^0"),
+        ("pi + e*x", "pi + e*x"),
+        ("series(k, 1, 4, k^2)*x", "30*x"),
+        ("series(k, 1, n, k*(2*3))", "series(k, 1, n, k*6)"),
+        ("int(t, 0, 1, 1/t)", "int(t, 0, 1, 1/t)"),
+    ] {
+        assert_eq!(folded(text), expected, "{}", text);
+    }
+}
+
+#[test]
+fn errors_are_kept() {
+    for (text, code) in [("y^0", "E301"), ("series(k, 1, 2.5, k)^0", "E303")] {
+        let expr = Expression::parse(text).unwrap();
+        let error = expr.eval(&Env::new()).unwrap_err();
+        assert_eq!(error.code(), code, "{}", text);
+    }
+}
+
+#[test]
+fn registered_functions_are_not_called() {
+    let mut context = Context::new();
+    context
+        .register_function("noise", Arity::Fixed(1), |args| args[0])
+        .unwrap();
+    let expr = Expression::parse_with("noise(2) + sqrt(4)", &context).unwrap();
+    assert_eq!(expr.simplify().to_string(), "noise(2) + 2");
+}
+
+#[test]
+fn common_subexpressions() {
+    let cases = [
+        ("sin(x)^2 + sin(x)*cos(x) + cos(x)^2", 2),
+        ("(x + 1)^2 + (x + 1)*y + x", 1),
+        ("exp(-(x - a)^2) + (x - a)^2 + exp(-(x - a)^2)", 2),
+        ("series(k, 1, 10, (x*k)^2 + (x*k)) + x*k", 1),
+        // shared before the larger subexpression containing it
+        (
+            "sin(exp(x^2)) + cos(exp(x^2) + 1) + cos(exp(x^2) + 1) + cos(exp(x^2) + 1)",
+            2,
+        ),
+        ("sin(x) + cos(x)", 0),
+        ("x + x + -y*-y", 0),
+    ];
+    let env = Env::new()
+        .with("x", 0.7)
+        .with("y", -1.3)
+        .with("a", 0.2)
+        .with("k", 4.0);
+    for (text, count) in cases {
+        let expr = Expression::parse(text).unwrap();
+        let optimized = optimize(expr.ast().clone());
+        assert_eq!(shared(&optimized), count, "{}", text);
+        // shared subexpressions are written out
+        assert_eq!(optimized.to_string(), expr.to_string(), "{}", text);
+        let lookup = |name: &str| env.get(name);
+        assert_eq!(
+            executor::eval(&optimized, &lookup).unwrap(),
+            executor::eval(expr.ast(), &lookup).unwrap(),
+            "{}",
+            text
+        );
+    }
+}
+
+#[test]
+fn errors_point_to_source() {
+    let expr = Expression::parse("a + sin(b)*sin(b)").unwrap();
+    let error = expr.eval(&Env::new()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        expr.eval(&Env::new()).unwrap_err().to_string()
+    );
+    let Error::Execution(error) = error else {
+        panic!("unexpected error {}", error);
+    };
+    assert_eq!(error.text(), "a");
+}
@@ -1,45 +1,57 @@
-use crate::colors::{bold_red, bold_yellow};
-use crate::solver_error::SolverError;
+use crate::solver_error::{Diagnostic, ErrorType};
 
-#[derive(Clone, Debug)]
-pub struct ParsingError<'src> {
-    pos: usize,
-    text: &'src str,
-    error_type: ParsingErrorType,
-}
+pub type ParsingError = Diagnostic<ParsingErrorType>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ParsingErrorType {
     UnmatchedParenthesis,
+    MissingOperand,
+    MissingOperator,
+    WrongArgs,
+    EmptyExpression,
+    UnexpectedComma,
+    ExpectedVariable,
 }
 
-impl<'src> ParsingError<'src> {
-    pub fn new(pos: usize, text: &'src str, error_type: ParsingErrorType) -> Self {
-        Self {
-            pos,
-            text,
-            error_type,
+impl ErrorType for ParsingErrorType {
+    fn code(&self) -> &'static str {
+        use ParsingErrorType::*;
+        match self {
+            UnmatchedParenthesis => "E201",
+            MissingOperand => "E202",
+            MissingOperator => "E203",
+            WrongArgs => "E204",
+            EmptyExpression => "E205",
+            UnexpectedComma => "E206",
+            ExpectedVariable => "E207",
         }
     }
-}
 
-impl SolverError for ParsingError<'_> {
-    fn display_solver_error(&self, source: &str) -> String {
+    fn message(&self) -> &'static str {
         use ParsingErrorType::*;
-
-        let msg = match self.error_type {
+        match self {
             UnmatchedParenthesis => "unmatched parethesis",
-        };
-
-        let brief = format!("{} `{}` at {}", msg, self.text, self.pos);
-        let cursor = " ".repeat(self.pos) + &"^".repeat(self.text.len());
+            MissingOperand => "missing operand for",
+            MissingOperator => "missing operator before",
+            WrongArgs => "wrong number of arguments for",
+            EmptyExpression => "empty expression",
+            UnexpectedComma => "comma outside of function arguments",
+            ExpectedVariable => "expected variable to bind instead of",
+        }
+    }
 
-        format!(
-            "{}: {}\n{}\n{}",
-            bold_red("error"),
-            brief,
-            source,
-            bold_yellow(cursor),
-        )
+    fn help(&self) -> Option<&'static str> {
+        use ParsingErrorType::*;
+        match self {
+            UnmatchedParenthesis => {
+                Some("every `(` needs a closing `)` and function arguments go in parentheses")
+            }
+            MissingOperator => Some("values must be joined with an operator, e.g. `2*x`"),
+            UnexpectedComma => Some("commas only separate function arguments, e.g. `max(1, 2)`"),
+            ExpectedVariable => {
+                Some("the variable comes first, e.g. `series(k, 1, n, k^2)` or `int(t, 0, x, t^2)`")
+            }
+            _ => None,
+        }
     }
 }
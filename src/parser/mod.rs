@@ -1,96 +1,403 @@
-use crate::tokens::{Token, TokenType};
+use crate::ast::{BinaryOp, BoundOp, Node, NodeKind, UnaryOp};
+use crate::context::Arity;
+use crate::tokens::{Span, Token, TokenType};
 use errors::{ParsingError, ParsingErrorType};
 
-mod errors;
+pub mod errors;
 
 #[cfg(test)]
 mod tests;
 
-pub fn parse<'src, I>(tokens: I) -> Result<Vec<Token<'src>>, ParsingError<'src>>
+/// Opened parenthesis: the operand stack height when it was opened, number of
+/// commas met inside it and whether it holds function call arguments
+struct Group {
+    base: usize,
+    commas: usize,
+    call: bool,
+}
+
+/// Builds expression tree from infix tokens with the shunting yard algorithm
+pub fn parse<'src, I>(tokens: I) -> Result<Node, ParsingError>
 where
     I: Iterator<Item = Token<'src>>,
 {
     use ParsingErrorType::*;
     use TokenType::*;
 
-    let mut tokens_it = tokens.peekable();
+    let mut tokens_it = tokens;
     let mut operator_stack: Vec<Token> = Vec::new();
-    let mut postfix_list: Vec<Token> = Vec::new();
+    let mut operands: Vec<Node> = Vec::new();
+    let mut groups: Vec<Group> = Vec::new();
     let mut prev_token: Option<Token> = None;
+    let mut expect_operand = true;
 
     while let Some(token) = tokens_it.next() {
         match token.token_type {
-            Number(_) | Var | Const(_) => postfix_list.push(token),
-            Func { .. } => match tokens_it.peek() {
-                Some(Token {
-                    token_type: LParen, ..
-                }) => operator_stack.push(token),
-                _ => {
-                    return Err(ParsingError::new(
-                        token.pos,
-                        token.text,
-                        UnmatchedParenthesis,
-                    ))
-                }
-            },
-            LParen => operator_stack.push(token),
-            RParen => loop {
-                match operator_stack.pop() {
-                    Some(Token {
+            Number(_) | Var(_) | Const(_) | Func(_) | UserFunc(_) | Bound(_) | LParen
+                if !expect_operand =>
+            {
+                return Err(get_parsing_err(token, MissingOperator));
+            }
+            Number(n) => operands.push(Node::new(NodeKind::Number(n), token.span())),
+            Var(name) => operands.push(Node::new(NodeKind::Var(name.into()), token.span())),
+            Const(value) => operands.push(Node::new(
+                NodeKind::Const {
+                    name: token.text.into(),
+                    value,
+                },
+                token.span(),
+            )),
+            Func(_) | UserFunc(_) | Bound(_) => match tokens_it.next() {
+                Some(
+                    paren @ Token {
                         token_type: LParen, ..
-                    }) => break,
-                    Some(tok) => postfix_list.push(tok),
-                    None => return Err(ParsingError::new(token.pos, ")", UnmatchedParenthesis)),
+                    },
+                ) => {
+                    operator_stack.push(token);
+                    operator_stack.push(paren);
+                    groups.push(Group {
+                        base: operands.len(),
+                        commas: 0,
+                        call: true,
+                    });
+                    prev_token = Some(paren);
+                    continue;
                 }
+                _ => return Err(get_parsing_err(token, UnmatchedParenthesis)),
             },
+            LParen => {
+                operator_stack.push(token);
+                groups.push(Group {
+                    base: operands.len(),
+                    commas: 0,
+                    call: false,
+                });
+                prev_token = Some(token);
+                continue;
+            }
+            Comma | RParen if expect_operand => {
+                let is_empty_call = matches!(token.token_type, RParen)
+                    && matches!(
+                        (prev_token, groups.last()),
+                        (
+                            Some(Token {
+                                token_type: LParen,
+                                ..
+                            }),
+                            Some(Group { call: true, .. })
+                        )
+                    );
+                if !is_empty_call {
+                    return Err(missing_operand_err(prev_token, token));
+                }
+                operator_stack.pop();
+                groups.pop();
+                let func = operator_stack.pop().unwrap();
+                operands.push(get_call(func, Vec::new(), token)?);
+            }
+            Comma => {
+                match groups.last_mut() {
+                    Some(group) if group.call => group.commas += 1,
+                    _ => return Err(get_parsing_err(token, UnexpectedComma)),
+                }
+                pop_group_operators(&mut operator_stack, &mut operands, token)?;
+                prev_token = Some(token);
+                expect_operand = true;
+                continue;
+            }
+            RParen => {
+                pop_group_operators(&mut operator_stack, &mut operands, token)?;
+                operator_stack.pop();
+                let group = groups.pop().unwrap();
+                if group.call {
+                    let func = operator_stack.pop().unwrap();
+                    let args = operands.split_off(group.base);
+                    debug_assert_eq!(args.len(), group.commas + 1);
+                    operands.push(get_call(func, args, token)?);
+                }
+            }
             _ => {
                 let actual_token = match token.token_type {
-                    Sub if !matches!(
-                        prev_token,
-                        Some(Token {
-                            token_type: Number(_) | Var | Const(_) | RParen,
-                            ..
-                        })
-                    ) =>
-                    {
-                        Token::new(token.pos, token.text, UM)
-                    }
+                    Sub if expect_operand => Token::new(token.pos, token.text, UM),
+                    Add if expect_operand => Token::new(token.pos, token.text, UP),
+                    _ if expect_operand => return Err(missing_operand_err(prev_token, token)),
                     _ => token,
                 };
-                loop {
-                    match operator_stack.last() {
-                        Some(operator) if get_prec(operator) >= get_prec(&actual_token) => {
-                            postfix_list.push(operator_stack.pop().unwrap());
-                        }
-                        _ => break operator_stack.push(actual_token),
+                let Some(info) = get_op_info(&actual_token) else {
+                    return Err(missing_operand_err(prev_token, token));
+                };
+                while let Some(operator) = operator_stack.last() {
+                    if !binds_before(operator, info) {
+                        break;
                     }
+                    let operator = operator_stack.pop().unwrap();
+                    apply_operator(operator, &mut operands)?;
                 }
+                operator_stack.push(actual_token);
                 prev_token = Some(actual_token);
+                expect_operand = true;
                 continue;
             }
         };
         prev_token = Some(token);
+        expect_operand = false;
+    }
+
+    if expect_operand {
+        return match prev_token {
+            Some(Token {
+                token_type: LParen, ..
+            }) => Err(get_parsing_err(prev_token.unwrap(), UnmatchedParenthesis)),
+            Some(token) => Err(get_parsing_err(token, MissingOperand)),
+            None => Err(ParsingError::new(0, "", EmptyExpression)),
+        };
     }
 
     while let Some(op) = operator_stack.pop() {
         match op.token_type {
-            LParen => return Err(ParsingError::new(op.pos, "(", UnmatchedParenthesis)),
-            _ => postfix_list.push(op),
+            LParen => return Err(get_parsing_err(op, UnmatchedParenthesis)),
+            _ => apply_operator(op, &mut operands)?,
         }
     }
 
-    Ok(postfix_list)
+    debug_assert_eq!(operands.len(), 1);
+    operands
+        .pop()
+        .ok_or_else(|| ParsingError::new(0, "", EmptyExpression))
 }
 
-fn get_prec(token: &Token) -> u64 {
+/// Applies operators up to the innermost opened parenthesis
+fn pop_group_operators(
+    operator_stack: &mut Vec<Token>,
+    operands: &mut Vec<Node>,
+    token: Token,
+) -> Result<(), ParsingError> {
+    loop {
+        match operator_stack.last() {
+            Some(Token {
+                token_type: TokenType::LParen,
+                ..
+            }) => return Ok(()),
+            Some(_) => {
+                let operator = operator_stack.pop().unwrap();
+                apply_operator(operator, operands)?;
+            }
+            None => {
+                return Err(get_parsing_err(
+                    token,
+                    ParsingErrorType::UnmatchedParenthesis,
+                ))
+            }
+        }
+    }
+}
+
+fn apply_operator(operator: Token, operands: &mut Vec<Node>) -> Result<(), ParsingError> {
+    let missing_operand = || get_parsing_err(operator, ParsingErrorType::MissingOperand);
+    let op = match operator.token_type {
+        TokenType::UM => {
+            let operand = operands.pop().ok_or_else(missing_operand)?;
+            let span = join_spans(operator.span(), operand.span);
+            let kind = NodeKind::Unary {
+                op: UnaryOp::Neg,
+                operand: Box::new(operand),
+            };
+            operands.push(Node::new(kind, span));
+            return Ok(());
+        }
+        TokenType::UP => {
+            let mut operand = operands.pop().ok_or_else(missing_operand)?;
+            operand.span = join_spans(operator.span(), operand.span);
+            operands.push(operand);
+            return Ok(());
+        }
+        TokenType::Add => BinaryOp::Add,
+        TokenType::Sub => BinaryOp::Sub,
+        TokenType::Mul => BinaryOp::Mul,
+        TokenType::Div => BinaryOp::Div,
+        TokenType::Pow => BinaryOp::Pow,
+        _ => return Err(missing_operand()),
+    };
+    let rhs = operands.pop().ok_or_else(missing_operand)?;
+    let lhs = operands.pop().ok_or_else(missing_operand)?;
+    let span = join_spans(lhs.span, rhs.span);
+    let kind = NodeKind::Binary {
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+    };
+    operands.push(Node::new(kind, span));
+    Ok(())
+}
+
+/// Call node of a native function, calls of user functions are replaced with
+/// their bodies. `sum` of a variable, two bounds and an expression is the
+/// same as `series` rather than the sum of its arguments
+fn get_call(func: Token, args: Vec<Node>, rparen: Token) -> Result<Node, ParsingError> {
+    let arity = match func.token_type {
+        TokenType::Func(_) if func.text == "sum" && is_binding(&args) => {
+            return get_bound(BoundOp::Series, func, args, rparen);
+        }
+        TokenType::Bound(op) => return get_bound(op, func, args, rparen),
+        TokenType::Func(function) => function.arity(),
+        TokenType::UserFunc(function) => Arity::Fixed(function.params().len()),
+        _ => unreachable!("call group is always opened after a function token"),
+    };
+    if !arity.accepts(args.len()) {
+        return Err(get_parsing_err(func, ParsingErrorType::WrongArgs)
+            .with_help(format!("`{}` takes {}", func.text, arity)));
+    }
+    let span = join_spans(func.span(), rparen.span());
+    Ok(match func.token_type {
+        TokenType::UserFunc(function) => function.expand(&args, span),
+        TokenType::Func(function) => {
+            let kind = NodeKind::Call {
+                name: func.text.into(),
+                func: function.clone(),
+                args,
+            };
+            Node::new(kind, span)
+        }
+        _ => unreachable!(),
+    })
+}
+
+fn is_binding(args: &[Node]) -> bool {
+    args.len() == 4 && matches!(args[0].kind, NodeKind::Var(_))
+}
+
+/// Node of operator `op` binding the variable given as the first argument
+fn get_bound(
+    op: BoundOp,
+    func: Token,
+    args: Vec<Node>,
+    rparen: Token,
+) -> Result<Node, ParsingError> {
+    if args.len() != 4 {
+        return Err(
+            get_parsing_err(func, ParsingErrorType::WrongArgs).with_help(format!(
+                "`{}` takes a variable, its bounds and an expression, e.g. `{}(k, 1, n, k^2)`",
+                func.text, func.text
+            )),
+        );
+    }
+    let mut args = args.into_iter();
+    let var = args.next().unwrap();
+    let NodeKind::Var(name) = var.kind else {
+        let text = var.to_string();
+        return Err(ParsingError::new(
+            var.span.pos,
+            &text,
+            ParsingErrorType::ExpectedVariable,
+        ));
+    };
+    let mut bounds_and_body = args.map(Box::new);
+    let kind = NodeKind::Bound {
+        op,
+        var: name,
+        from: bounds_and_body.next().unwrap(),
+        to: bounds_and_body.next().unwrap(),
+        body: bounds_and_body.next().unwrap(),
+    };
+    Ok(Node::new(kind, join_spans(func.span(), rparen.span())))
+}
+
+fn join_spans(first: Span, last: Span) -> Span {
+    Span::new(first.pos, last.end().max(first.end()) - first.pos)
+}
+
+fn missing_operand_err(prev_token: Option<Token>, token: Token) -> ParsingError {
+    get_parsing_err(
+        prev_token.unwrap_or(token),
+        ParsingErrorType::MissingOperand,
+    )
+}
+
+fn get_parsing_err(token: Token, error_type: ParsingErrorType) -> ParsingError {
+    ParsingError::new(token.pos, token.text, error_type)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fixity {
+    Prefix,
+    Infix,
+}
+
+/// How tightly an operator binds, which way chains of it group and where it
+/// stands relative to its operands
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpInfo {
+    pub prec: u8,
+    pub assoc: Assoc,
+    pub fixity: Fixity,
+}
+
+impl OpInfo {
+    const fn infix(prec: u8, assoc: Assoc) -> Self {
+        Self {
+            prec,
+            assoc,
+            fixity: Fixity::Infix,
+        }
+    }
+
+    const fn prefix(prec: u8) -> Self {
+        Self {
+            prec,
+            assoc: Assoc::Right,
+            fixity: Fixity::Prefix,
+        }
+    }
+}
+
+/// Unary minus and plus bind weaker than `^` so that `-x^2 == -(x^2)`, but
+/// tighter than `*` and `/`
+const PREFIX: OpInfo = OpInfo::prefix(3);
+
+pub fn binary_op_info(op: BinaryOp) -> OpInfo {
+    use BinaryOp::*;
+    match op {
+        Add | Sub => OpInfo::infix(1, Assoc::Left),
+        Mul | Div => OpInfo::infix(2, Assoc::Left),
+        Pow => OpInfo::infix(4, Assoc::Right),
+    }
+}
+
+pub fn unary_op_info(op: UnaryOp) -> OpInfo {
+    match op {
+        UnaryOp::Neg => PREFIX,
+    }
+}
+
+fn get_op_info(token: &Token) -> Option<OpInfo> {
     use TokenType::*;
     match token.token_type {
-        UM => 5,
-        Pow => 4,
-        Mul | Div => 3,
-        Add | Sub => 2,
-        RParen | Comma => 1,
-        LParen => 0,
-        _ => 10,
+        UM | UP => Some(PREFIX),
+        Add => Some(binary_op_info(BinaryOp::Add)),
+        Sub => Some(binary_op_info(BinaryOp::Sub)),
+        Mul => Some(binary_op_info(BinaryOp::Mul)),
+        Div => Some(binary_op_info(BinaryOp::Div)),
+        Pow => Some(binary_op_info(BinaryOp::Pow)),
+        _ => None,
+    }
+}
+
+/// Whether operator on top of the stack has to be applied before `incoming`
+/// is pushed
+fn binds_before(top: &Token, incoming: OpInfo) -> bool {
+    let Some(top) = get_op_info(top) else {
+        return false;
+    };
+    match incoming.fixity {
+        Fixity::Prefix => false,
+        Fixity::Infix => {
+            top.prec > incoming.prec || (top.prec == incoming.prec && incoming.assoc == Assoc::Left)
+        }
     }
 }
@@ -1,86 +1,250 @@
 use super::*;
 use crate::lexer::analyse;
-use TokenType::*;
+use crate::solver_error::SolverError;
+
+fn parse_text(text: &str) -> Result<Node, ParsingError> {
+    parse(analyse(text).map(|res| res.unwrap()))
+}
 
 #[test]
 fn it_works() {
     let text = "2 + 2 * sin(3 ^ -3)";
-    let tokens_it = analyse(text).map(|res| res.unwrap());
-    let postfix_list = parse(tokens_it).unwrap();
-    println!("{:#?}", postfix_list);
-
-    assert_eq!(
-        postfix_list[0],
-        Token {
-            pos: 0,
-            text: "2",
-            token_type: Number(2.0),
-        }
-    );
-    assert_eq!(
-        postfix_list[1],
-        Token {
-            pos: 4,
-            text: "2",
-            token_type: Number(2.0)
-        }
-    );
-    assert_eq!(
-        postfix_list[2],
-        Token {
-            pos: 12,
-            text: "3",
-            token_type: Number(3.0)
-        }
-    );
-    assert_eq!(
-        postfix_list[3],
-        Token {
-            pos: 17,
-            text: "3",
-            token_type: Number(3.0)
-        }
-    );
-    assert_eq!(
-        postfix_list[4],
-        Token {
-            pos: 16,
-            text: "-",
-            token_type: UM
+    let ast = parse_text(text).unwrap();
+    println!("{:#?}", ast);
+
+    assert_eq!(ast.to_string(), "2 + 2*sin(3^-3)");
+    assert_eq!(ast.span, Span::new(0, 19));
+
+    let NodeKind::Binary {
+        op: BinaryOp::Add,
+        lhs,
+        rhs,
+    } = ast.kind
+    else {
+        panic!("wrong root node");
+    };
+    assert!(matches!(lhs.kind, NodeKind::Number(n) if n == 2.0));
+    assert_eq!(lhs.span, Span::new(0, 1));
+
+    let NodeKind::Binary {
+        op: BinaryOp::Mul,
+        rhs: call,
+        ..
+    } = rhs.kind
+    else {
+        panic!("wrong product node");
+    };
+    assert_eq!(call.span, Span::new(8, 11));
+
+    let NodeKind::Call { name, args, .. } = call.kind else {
+        panic!("wrong call node");
+    };
+    assert_eq!(name, "sin");
+    assert_eq!(args.len(), 1);
+    assert_eq!(args[0].span, Span::new(12, 6));
+
+    let NodeKind::Binary {
+        op: BinaryOp::Pow,
+        rhs: exponent,
+        ..
+    } = &args[0].kind
+    else {
+        panic!("wrong power node");
+    };
+    assert!(matches!(
+        exponent.kind,
+        NodeKind::Unary {
+            op: UnaryOp::Neg,
+            ..
         }
-    );
-    assert_eq!(
-        postfix_list[5],
-        Token {
-            pos: 14,
-            text: "^",
-            token_type: Pow
+    ));
+    assert_eq!(exponent.span, Span::new(16, 2));
+}
+
+#[test]
+fn nested_calls() {
+    let ast = parse_text("max(1, min(2, 3)) + hypot(max(1, 2), -4)").unwrap();
+    assert_eq!(ast.to_string(), "max(1, min(2, 3)) + hypot(max(1, 2), -4)");
+
+    let ast = parse_text("clamp(atan2(1, 2) * 3, -(1), pow(2, 3 - 1))").unwrap();
+    assert_eq!(ast.to_string(), "clamp(atan2(1, 2)*3, -1, pow(2, 3 - 1))");
+}
+
+#[test]
+fn bound_operators() {
+    let ast = parse_text("1 + series(k, 1, n, 1/k^2)").unwrap();
+    assert_eq!(ast.to_string(), "1 + series(k, 1, n, 1/k^2)");
+    let NodeKind::Binary { rhs, .. } = ast.kind else {
+        panic!("wrong root node");
+    };
+    assert_eq!(rhs.span, Span::new(4, 22));
+    let NodeKind::Bound { op, var, body, .. } = rhs.kind else {
+        panic!("wrong bound node");
+    };
+    assert_eq!((op, var.as_str()), (BoundOp::Series, "k"));
+    assert_eq!(body.to_string(), "1/k^2");
+
+    let ast = parse_text("int(t, 0, x, prod(k, 1, 3, t + k))").unwrap();
+    assert!(matches!(
+        ast.kind,
+        NodeKind::Bound {
+            op: BoundOp::Int,
+            ..
         }
-    );
+    ));
 
+    // `sum` of a variable and three arguments is `series`
+    let ast = parse_text("sum(k, 1, 100, 1/k^2)").unwrap();
     assert!(matches!(
-        postfix_list[6],
-        Token {
-            pos: 8,
-            text: "sin",
-            token_type: Func { args: 1, .. }
+        ast.kind,
+        NodeKind::Bound {
+            op: BoundOp::Series,
+            ..
         }
     ));
+    assert_eq!(ast.to_string(), "series(k, 1, 100, 1/k^2)");
+    for text in ["sum(k, 1, 2)", "sum(1, k, 2, 3)", "sum(k, 1, 2, 3, 4)"] {
+        let ast = parse_text(text).unwrap();
+        assert!(matches!(ast.kind, NodeKind::Call { .. }), "{}", text);
+    }
+}
+
+#[test]
+fn minimal_parentheses() {
+    for (text, expected) in [
+        ("(1 - 2) - (3 - 4)", "1 - 2 - (3 - 4)"),
+        ("((x))", "x"),
+        ("(a + b) * (c / d)", "(a + b)*(c/d)"),
+        ("-(x + 1)", "-(x + 1)"),
+    ] {
+        assert_eq!(parse_text(text).unwrap().to_string(), expected);
+    }
+}
 
-    assert_eq!(
-        postfix_list[7],
-        Token {
-            pos: 6,
-            text: "*",
-            token_type: Mul
+#[test]
+fn errors() {
+    use ParsingErrorType::*;
+
+    for (text, error_type, pos) in [
+        ("", EmptyExpression, 0),
+        ("(2 + 3", UnmatchedParenthesis, 0),
+        ("2 + 3)", UnmatchedParenthesis, 5),
+        ("sin 2", UnmatchedParenthesis, 0),
+        ("sin(", UnmatchedParenthesis, 3),
+        ("2 3", MissingOperator, 2),
+        ("2 (3)", MissingOperator, 2),
+        ("2 +", MissingOperand, 2),
+        ("* 2", MissingOperand, 0),
+        ("max(1, )", MissingOperand, 5),
+        ("()", MissingOperand, 0),
+        ("max()", WrongArgs, 0),
+        ("atan2(1)", WrongArgs, 0),
+        ("sin(1, 2)", WrongArgs, 0),
+        ("sin()", WrongArgs, 0),
+        ("int(t, 0, 1)", WrongArgs, 0),
+        ("prod(k, 1, 2, k, 3)", WrongArgs, 0),
+        ("1 + prod(2, 1, 2, k)", ExpectedVariable, 9),
+        ("int(pi, 0, 1, x)", ExpectedVariable, 4),
+        ("1, 2", UnexpectedComma, 1),
+        ("(1, 2)", UnexpectedComma, 2),
+    ] {
+        let error = parse_text(text).unwrap_err();
+        assert_eq!(error.error_type(), &error_type, "{}", text);
+        assert_eq!(error.span().pos, pos, "{}", text);
+    }
+}
+
+/// Fully parenthesized form of the tree
+fn grouping(node: &Node) -> String {
+    match &node.kind {
+        NodeKind::Unary { operand, .. } => format!("(-{})", grouping(operand)),
+        NodeKind::Binary { op, lhs, rhs } => {
+            format!("({}{}{})", grouping(lhs), op.symbol(), grouping(rhs))
+        }
+        NodeKind::Call { name, args, .. } => {
+            let args: Vec<String> = args.iter().map(grouping).collect();
+            format!("{}({})", name, args.join(","))
         }
-    );
-    assert_eq!(
-        postfix_list[8],
-        Token {
-            pos: 2,
-            text: "+",
-            token_type: Add
+        NodeKind::Bound {
+            op,
+            var,
+            from,
+            to,
+            body,
+        } => {
+            let args = [from, to, body].map(|arg| grouping(arg));
+            format!("{}({},{})", op.name(), var, args.join(","))
         }
-    );
+        _ => node.to_string(),
+    }
+}
+
+#[test]
+fn conventions() {
+    for (text, expected) in [
+        ("2^3^2", "(2^(3^2))"),
+        ("-x^2", "(-(x^2))"),
+        ("-2^2", "(-(2^2))"),
+        ("2^-3", "(2^(-3))"),
+        ("2^-x^2", "(2^(-(x^2)))"),
+        ("(-x)^2", "((-x)^2)"),
+        ("-x*y", "((-x)*y)"),
+        ("-x/2", "((-x)/2)"),
+        ("--x", "(-(-x))"),
+        ("+x - +y", "(x-y)"),
+        ("1 - 2 - 3", "((1-2)-3)"),
+        ("8 / 4 / 2", "((8/4)/2)"),
+        ("8 / 4 * 2", "((8/4)*2)"),
+        ("1 + 2 * 3", "(1+(2*3))"),
+        ("2 * 3^2", "(2*(3^2))"),
+        ("a - -b", "(a-(-b))"),
+        ("-sin(x)^2", "(-(sin(x)^2))"),
+    ] {
+        let ast = parse_text(text).unwrap();
+        assert_eq!(grouping(&ast), expected, "{}", text);
+    }
+}
+
+#[test]
+fn conventional_values() {
+    use crate::executor::eval;
+
+    for (text, expected) in [
+        ("2^3^2", 512.0),
+        ("-2^2", -4.0),
+        ("-x^2", -9.0),
+        ("2^-1", 0.5),
+        ("-2*3", -6.0),
+        ("10 - 4 - 3", 3.0),
+        ("64 / 8 / 2", 4.0),
+    ] {
+        let ast = parse_text(text).unwrap();
+        let value = eval(&ast, &|name| (name == "x").then_some(3.0)).unwrap();
+        assert_eq!(value, expected, "{}", text);
+    }
+}
+
+#[test]
+fn printing_round_trips() {
+    for text in [
+        "2^3^2",
+        "(2^3)^2",
+        "-x^2",
+        "(-x)^2",
+        "(-2)^2",
+        "2^-x",
+        "a - (b - c)",
+        "a/(b*c)",
+        "-(a + b)*c",
+        "series(k, -1, n^2, -k^2)",
+    ] {
+        let ast = parse_text(text).unwrap();
+        let printed = ast.to_string();
+        assert_eq!(
+            grouping(&parse_text(&printed).unwrap()),
+            grouping(&ast),
+            "{}",
+            text
+        );
+    }
 }
@@ -0,0 +1,247 @@
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use solver::executor::errors::ExecutionErrorType;
+use solver::solver_error::SolverError;
+use solver::solvers::{integral, root, IntegralMethod, RootMethod};
+use solver::{Context, Env, Error, Expression};
+use std::path::PathBuf;
+
+use crate::is_var_name;
+
+const REPL_EPS: f64 = 0.000_001;
+const HISTORY_FILE: &str = ".solver_history";
+const HELP: &str = "\
+<expr>                  evaluate expression, the result is kept in `ans`
+<name> = <expr>         assign variable
+<name>(<params>) = <expr>
+                        define function, e.g. `f(x, y) = x*y`
+:root <expr> <x1> <x2>  find root of <expr> with variable `x` between <x1> and <x2>
+:integral <expr> <x1> <x2>
+                        find definite integral of <expr> with variable `x`
+--wrt <var>             after :root or :integral picks the variable instead of `x`
+:help                   print this message
+:quit                   leave the session, same as Ctrl-D
+Arguments containing spaces have to be quoted: :root \"x^2 - 2\" 0 2";
+
+/// Reads lines until end of input, evaluating them with variables from `env`
+/// and definitions from `context` which persist for the session. History is
+/// kept in `~/.solver_history`
+pub fn run(mut env: Env, mut context: Context) {
+    let mut editor = DefaultEditor::new().unwrap_or_else(|e| {
+        eprintln!("error: can not start the session: {}", e);
+        std::process::exit(1);
+    });
+    let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+    if let Some(path) = &history {
+        // there is no history before the first session
+        let _ = editor.load_history(path);
+    }
+
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                break;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+        if matches!(line, ":quit" | ":q") {
+            break;
+        }
+        match execute(line, &mut env, &mut context) {
+            Ok(Some(value)) => println!("{}", value),
+            Ok(None) => {}
+            Err(message) => eprintln!("{}", message),
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Err(e) = editor.save_history(path) {
+            eprintln!("warning: can not save history: {}", e);
+        }
+    }
+}
+
+/// Runs a single line, the printable error is returned with the source
+/// rendered
+fn execute(line: &str, env: &mut Env, context: &mut Context) -> Result<Option<f64>, String> {
+    if let Some(command) = line.strip_prefix(':') {
+        let args = split_args(command)?;
+        let (name, args) = args.split_first().ok_or("error: command expected")?;
+        let value = match name.as_str() {
+            "help" => {
+                println!("{}", HELP);
+                return Ok(None);
+            }
+            "root" | "integral" => solve(name, args, env, context)?,
+            _ => return Err(format!("error: unknown command `:{}`, see `:help`", name)),
+        };
+        env.set("ans", value);
+        return Ok(Some(value));
+    }
+
+    let (name, expr) = match line.split_once('=') {
+        Some((head, _)) if head.contains('(') => {
+            context.define(line).map_err(|e| render(e, line))?;
+            return Ok(None);
+        }
+        Some((name, expr)) => (Some(name.trim()), expr),
+        None => (None, line),
+    };
+    if let Some(name) = name {
+        if !is_var_name(name, context) {
+            return Err(format!(
+                "error: `{}` can not be used as variable name",
+                name
+            ));
+        }
+    }
+    let value = eval(expr, env, context)?;
+    if let Some(name) = name {
+        env.set(name, value);
+    }
+    env.set("ans", value);
+    Ok(Some(value))
+}
+
+/// `:root` and `:integral` with default method and eps, `--wrt <var>` picks
+/// the variable like in the command line
+fn solve(command: &str, args: &[String], env: &Env, context: &Context) -> Result<f64, String> {
+    let usage = || {
+        format!(
+            "error: usage is `:{} <expr> <x1> <x2> [--wrt <var>]`",
+            command
+        )
+    };
+    let (args, wrt) = match args.iter().position(|arg| arg == "--wrt") {
+        Some(i) => {
+            let wrt = args.get(i + 1).ok_or_else(usage)?;
+            let rest = [&args[..i], &args[i + 2..]].concat();
+            (rest, wrt.as_str())
+        }
+        None => (args.to_vec(), "x"),
+    };
+    let [expr, x1, x2] = args.as_slice() else {
+        return Err(usage());
+    };
+    if !is_var_name(wrt, context) {
+        return Err(format!("error: `{}` can not be used as variable name", wrt));
+    }
+    let x1 = eval(x1, env, context)?;
+    let x2 = eval(x2, env, context)?;
+    let expression = Expression::parse_with(expr, context).map_err(|e| render(e, expr))?;
+    let f = expression.closure(wrt, env).map_err(|e| render(e, expr))?;
+    let report = match command {
+        "root" => root(f, x1, x2, REPL_EPS, None, RootMethod::default()),
+        _ => integral(f, x1, x2, REPL_EPS, None, IntegralMethod::default()),
+    };
+    report
+        .ok()
+        .ok_or_else(|| format!("could not find {}: {}", command, report.termination))
+}
+
+fn eval(expr: &str, env: &Env, context: &Context) -> Result<f64, String> {
+    let expression = Expression::parse_with(expr, context).map_err(|e| render(e, expr))?;
+    expression.eval(env).map_err(|e| render(e, expr))
+}
+
+fn render(error: Error, source: &str) -> String {
+    // variables are assigned in the session rather than bound with `--var`
+    let error = match error {
+        Error::Execution(e) if e.error_type() == &ExecutionErrorType::UnknownIdent => {
+            let help = format!("assign it with `{} = <value>`", e.text());
+            Error::Execution(e.with_help(help))
+        }
+        error => error,
+    };
+    error.display_solver_error(source)
+}
+
+/// Splits command arguments on whitespace, double quotes group an argument
+fn split_args(command: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quoted = false;
+    for c in command.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => args.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        return Err("error: unterminated quote".to_string());
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session() {
+        let mut env = Env::new();
+        let mut context = Context::new();
+        assert_eq!(execute("a = 3.2", &mut env, &mut context), Ok(Some(3.2)));
+        assert_eq!(execute("2*a", &mut env, &mut context), Ok(Some(6.4)));
+        assert_eq!(execute("ans + 1", &mut env, &mut context), Ok(Some(7.4)));
+        assert_eq!(env.get("a"), Some(3.2));
+
+        let root = execute(":root \"x^2 - a\" 0 a", &mut env, &mut context)
+            .unwrap()
+            .unwrap();
+        assert!(f64::abs(root - f64::sqrt(3.2)) < REPL_EPS);
+        let integral = execute(":integral x 0 ans", &mut env, &mut context)
+            .unwrap()
+            .unwrap();
+        assert!(f64::abs(integral - 1.6) < REPL_EPS);
+
+        assert!(execute("2 +", &mut env, &mut context).is_err());
+        assert!(execute("pi = 3", &mut env, &mut context).is_err());
+        assert!(execute(":root x 1", &mut env, &mut context).is_err());
+        assert!(execute(":root t 0 1 --wrt", &mut env, &mut context).is_err());
+        assert!(execute(":root t 0 1 --wrt pi", &mut env, &mut context).is_err());
+        let t = execute(":root \"t - 0.5\" 0 1 --wrt t", &mut env, &mut context)
+            .unwrap()
+            .unwrap();
+        assert!(f64::abs(t - 0.5) < REPL_EPS);
+        let integral = execute(":integral --wrt t t 0 1", &mut env, &mut context)
+            .unwrap()
+            .unwrap();
+        assert!(f64::abs(integral - 0.5) < REPL_EPS);
+        assert!(execute(":foo", &mut env, &mut context).is_err());
+        assert_eq!(env.get("ans"), Some(integral));
+
+        assert_eq!(
+            execute("f(x, y) = x*y + a", &mut env, &mut context),
+            Ok(None)
+        );
+        assert_eq!(
+            execute("b = f(2, 1)", &mut env, &mut context),
+            Ok(Some(5.2))
+        );
+        assert!(execute("g(x) = g(x)", &mut env, &mut context).is_err());
+        assert!(execute("f = 1", &mut env, &mut context).is_err());
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(
+            split_args(" root  \"x - 1\" 0 \"\" 2").unwrap(),
+            ["root", "x - 1", "0", "", "2"]
+        );
+        assert!(split_args("root \"x").is_err());
+    }
+}
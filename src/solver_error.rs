@@ -1,10 +1,307 @@
-use std::process::exit;
+use std::fmt::{self, Display};
 
-pub fn print_error(error: impl SolverError, source: &str) {
-    eprintln!("{}", error.display_solver_error(source));
-    exit(1);
+use crate::colors::{bold_blue, bold_red, bold_yellow};
+use crate::context::errors::DefinitionError;
+use crate::diff::errors::DiffError;
+use crate::executor::errors::ExecutionError;
+use crate::lexer::errors::AnalyseError;
+use crate::parser::errors::ParsingError;
+use crate::tokens::Span;
+
+/// Kind of error reported by one of the stages
+pub trait ErrorType {
+    /// Stable code identifying the error kind, e.g. `E101`
+    fn code(&self) -> &'static str;
+    fn message(&self) -> &'static str;
+    fn help(&self) -> Option<&'static str> {
+        None
+    }
 }
 
 pub trait SolverError {
-    fn display_solver_error(&self, source: &str) -> String;
+    fn code(&self) -> &'static str;
+    fn span(&self) -> Span;
+    fn help(&self) -> Option<&str>;
+    /// One line description of the error without source context
+    fn brief(&self) -> String;
+
+    fn display_solver_error(&self, source: &str) -> String {
+        let width = source.split('\n').count().to_string().len();
+        let title = bold_red(format!("error[{}]", self.code()));
+        let mut out = format!("{}: {}", title, self.brief());
+        out += &render_span(self.span(), source, width);
+        if let Some(help) = self.help() {
+            out += &format!(
+                "\n{}{} {}",
+                " ".repeat(width + 1),
+                bold_blue("= help:"),
+                help
+            );
+        }
+        out
+    }
+}
+
+/// Draws the source lines covered by `span` with carets underneath
+fn render_span(span: Span, source: &str, width: usize) -> String {
+    let start = floor_char_boundary(source, span.pos);
+    let end = floor_char_boundary(source, span.end()).max(start);
+
+    let first_line = source[..start].matches('\n').count();
+    let lines: Vec<&str> = source.split('\n').collect();
+    let gutter = |line: &str| bold_blue(format!("{:>width$} |", line));
+
+    let col = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let col = source[col..start].chars().count() + 1;
+    let mut out = format!(
+        "\n{}{} {}:{}",
+        " ".repeat(width),
+        bold_blue("-->"),
+        first_line + 1,
+        col
+    );
+    out += &format!("\n{}", gutter(""));
+
+    let mut line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    for (n, line) in lines.iter().enumerate().skip(first_line) {
+        let line_end = line_start + line.len();
+        let from = start.max(line_start) - line_start;
+        let to = end.min(line_end).max(start.max(line_start)) - line_start;
+        let offset = line[..from].chars().count();
+        let carets = line[from..to].chars().count().max(1);
+
+        out += &format!("\n{} {}", gutter(&(n + 1).to_string()), line);
+        out += &format!(
+            "\n{} {}{}",
+            gutter(""),
+            " ".repeat(offset),
+            bold_yellow("^".repeat(carets))
+        );
+
+        if end <= line_end {
+            break;
+        }
+        line_start = line_end + 1;
+    }
+    out
+}
+
+fn floor_char_boundary(source: &str, pos: usize) -> usize {
+    let mut pos = pos.min(source.len());
+    while !source.is_char_boundary(pos) {
+        pos -= 1;
+    }
+    pos
+}
+
+/// Error of a single stage: analysis, parsing, execution or differentiation
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic<T: ErrorType> {
+    error_type: T,
+    span: Span,
+    text: String,
+    help: Option<String>,
+}
+
+impl<T: ErrorType> Diagnostic<T> {
+    pub fn new(pos: usize, text: &str, error_type: T) -> Self {
+        Self {
+            help: error_type.help().map(String::from),
+            error_type,
+            span: Span::new(pos, text.len()),
+            text: text.to_string(),
+        }
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn error_type(&self) -> &T {
+        &self.error_type
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Moves the span by `offset` when the source was a part of a longer text
+    pub(crate) fn shift(mut self, offset: usize) -> Self {
+        self.span.pos += offset;
+        self
+    }
+}
+
+impl<T: ErrorType> SolverError for Diagnostic<T> {
+    fn code(&self) -> &'static str {
+        self.error_type.code()
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn help(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+
+    fn brief(&self) -> String {
+        match self.text.as_str() {
+            "" => format!("{} at {}", self.error_type.message(), self.span.pos),
+            text => format!(
+                "{} `{}` at {}",
+                self.error_type.message(),
+                text,
+                self.span.pos
+            ),
+        }
+    }
+}
+
+impl<T: ErrorType> Display for Diagnostic<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.brief())
+    }
+}
+
+impl<T: ErrorType + fmt::Debug> std::error::Error for Diagnostic<T> {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    Analyse(AnalyseError),
+    Parsing(ParsingError),
+    Execution(ExecutionError),
+    Diff(DiffError),
+    Definition(DefinitionError),
+}
+
+impl Error {
+    fn inner(&self) -> &dyn SolverError {
+        match self {
+            Error::Analyse(error) => error,
+            Error::Parsing(error) => error,
+            Error::Execution(error) => error,
+            Error::Diff(error) => error,
+            Error::Definition(error) => error,
+        }
+    }
+
+    pub(crate) fn shift(self, offset: usize) -> Self {
+        match self {
+            Error::Analyse(error) => Error::Analyse(error.shift(offset)),
+            Error::Parsing(error) => Error::Parsing(error.shift(offset)),
+            Error::Execution(error) => Error::Execution(error.shift(offset)),
+            Error::Diff(error) => Error::Diff(error.shift(offset)),
+            Error::Definition(error) => Error::Definition(error.shift(offset)),
+        }
+    }
+}
+
+impl SolverError for Error {
+    fn code(&self) -> &'static str {
+        self.inner().code()
+    }
+
+    fn span(&self) -> Span {
+        self.inner().span()
+    }
+
+    fn help(&self) -> Option<&str> {
+        self.inner().help()
+    }
+
+    fn brief(&self) -> String {
+        self.inner().brief()
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.brief())
+    }
+}
+
+/// Has no source, the wrapped diagnostic has the same message and error
+/// chains would print it twice
+impl std::error::Error for Error {}
+
+impl From<AnalyseError> for Error {
+    fn from(error: AnalyseError) -> Self {
+        Error::Analyse(error)
+    }
+}
+
+impl From<ParsingError> for Error {
+    fn from(error: ParsingError) -> Self {
+        Error::Parsing(error)
+    }
+}
+
+impl From<ExecutionError> for Error {
+    fn from(error: ExecutionError) -> Self {
+        Error::Execution(error)
+    }
+}
+
+impl From<DiffError> for Error {
+    fn from(error: DiffError) -> Self {
+        Error::Diff(error)
+    }
+}
+
+impl From<DefinitionError> for Error {
+    fn from(error: DefinitionError) -> Self {
+        Error::Definition(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::errors::AnalyseErrorType;
+    use crate::parser::errors::ParsingErrorType;
+
+    fn strip_colors(text: &str) -> String {
+        let mut out = String::new();
+        let mut chars = text.chars();
+        while let Some(ch) = chars.next() {
+            if ch == '\x1B' {
+                chars.by_ref().take_while(|&c| c != 'm').for_each(drop);
+            } else {
+                out.push(ch);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn it_works() {
+        let error: Error = AnalyseError::new(4, "foo", AnalyseErrorType::UnknownFunction).into();
+
+        assert_eq!(error.code(), "E102");
+        assert_eq!(error.span(), Span::new(4, 3));
+        assert!(error.help().is_some());
+        assert_eq!(error.to_string(), "unknown function `foo` at 4");
+        assert!(std::error::Error::source(&error).is_none());
+        assert_eq!(
+            strip_colors(&error.display_solver_error("2 * foo")),
+            format!(
+                "error[E102]: unknown function `foo` at 4\n --> 1:5\n  |\n1 | 2 * foo\n  |     ^^^\n  = help: {}",
+                error.help().unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn multiline_span() {
+        let error = ParsingError::new(4, "(2 +\n 3", ParsingErrorType::UnmatchedParenthesis)
+            .with_help("custom help");
+        let rendered = strip_colors(&error.display_solver_error("1 + (2 +\n 3\n"));
+
+        assert_eq!(
+            rendered,
+            "error[E201]: unmatched parethesis `(2 +\n 3` at 4\n --> 1:5\n  |\n1 | 1 + (2 +\n  |     ^^^^\n2 |  3\n  | ^^\n  = help: custom help"
+        );
+    }
 }
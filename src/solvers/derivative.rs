@@ -0,0 +1,106 @@
+use super::report::{Counter, Report, Resolution, Termination};
+
+/// Default number of rows of the extrapolation tableau
+const DERIVATIVE_MAX_ROWS: usize = 10;
+/// Initial step relative to `max(|x|, 1)`
+const DERIVATIVE_STEP: f64 = 0.1;
+/// Step is divided by this factor on every row
+const STEP_RATIO: f64 = 1.4;
+/// Tableau stops once its new row is this much worse than the best estimate
+const SAFE: f64 = 2.0;
+/// How many times the initial step may be halved to get finite samples
+const MAX_HALVINGS: usize = 50;
+
+/// Derivative of `order` of `f` at `x` from central differences refined with
+/// Richardson extrapolation on a decreasing sequence of steps (Ridders'
+/// method). The initial step is halved until the difference is finite, so
+/// that points close to the edge of the domain work. Stops once the error
+/// estimate is below `eps` or starts to grow due to rounding, `max_iterations`
+/// limits the number of steps tried, 10 by default
+pub fn derivative(
+    f: impl Fn(f64) -> f64,
+    x: f64,
+    order: usize,
+    eps: f64,
+    max_iterations: Option<usize>,
+) -> Report {
+    let max_rows = max_iterations.unwrap_or(DERIVATIVE_MAX_ROWS).max(1);
+    let counter = Counter::new(f);
+    if order == 0 {
+        let value = counter.call(x);
+        let report = Report::new(Termination::Converged, value, 0.0, 0, Resolution::Step(0.0));
+        return counter.finish(report);
+    }
+    let difference = |h: f64| central_difference(&counter, x, order, h);
+
+    let mut h = DERIVATIVE_STEP * x.abs().max(1.0);
+    let mut first = difference(h);
+    for _ in 0..MAX_HALVINGS {
+        if first.is_finite() {
+            break;
+        }
+        h *= 0.5;
+        first = difference(h);
+    }
+
+    let (mut best, mut error, mut best_step) = (first, f64::INFINITY, h);
+    let mut previous = vec![first];
+    let mut termination = Termination::MaxIterations;
+    let mut iterations = 1;
+    for row in 1..max_rows {
+        iterations += 1;
+        h /= STEP_RATIO;
+        let mut current = Vec::with_capacity(row + 1);
+        current.push(difference(h));
+        // the error of central differences is a series in even powers of `h`
+        let mut factor = STEP_RATIO * STEP_RATIO;
+        for j in 1..=row {
+            let value = (current[j - 1] * factor - previous[j - 1]) / (factor - 1.0);
+            factor *= STEP_RATIO * STEP_RATIO;
+            let estimate = f64::max(
+                (value - current[j - 1]).abs(),
+                (value - previous[j - 1]).abs(),
+            );
+            if estimate <= error {
+                (best, error, best_step) = (value, estimate, h);
+            }
+            current.push(value);
+        }
+        let diverged = (current[row] - previous[row - 1]).abs() >= SAFE * error;
+        previous = current;
+        if error <= eps {
+            termination = Termination::Converged;
+            break;
+        }
+        if diverged {
+            break;
+        }
+    }
+    let report = Report::new(
+        termination,
+        best,
+        error,
+        iterations,
+        Resolution::Step(best_step),
+    );
+    counter.finish(report)
+}
+
+/// Central difference of `order` with step `h`, the points are symmetric
+/// around `x` and `h` apart
+fn central_difference<F: Fn(f64) -> f64>(
+    counter: &Counter<F>,
+    x: f64,
+    order: usize,
+    h: f64,
+) -> f64 {
+    let half = 0.5 * order as f64;
+    let mut binomial = 1.0;
+    let mut sum = 0.0;
+    for k in 0..=order {
+        let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+        sum += sign * binomial * counter.call(x + (half - k as f64) * h);
+        binomial = binomial * (order - k) as f64 / (k + 1) as f64;
+    }
+    sum / h.powi(order as i32)
+}
@@ -0,0 +1,517 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use super::report::{Counter, Report, Resolution, Termination};
+
+const INT_MAX_ITER: usize = 100_000;
+
+/// Quadrature rule. Adaptive methods refine the subintervals with the largest
+/// error estimate, `max_iterations` limits the number of subdivisions for them
+/// and the number of points per sweep for `trapezoid` and `romberg`, the
+/// number of step halvings for `tanh-sinh`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntegralMethod {
+    /// Repeated halving with trapezoid and midpoint sums
+    Trapezoid,
+    /// Adaptive Simpson's rule with Richardson correction
+    Simpson,
+    /// Adaptive 7-point Gauss and 15-point Kronrod pair
+    #[default]
+    GaussKronrod,
+    /// Richardson extrapolation of the trapezoid sums
+    Romberg,
+    /// Double exponential substitution, never evaluates interval ends and
+    /// copes with singularities there
+    TanhSinh,
+}
+
+impl IntegralMethod {
+    pub const ALL: [IntegralMethod; 5] = [
+        IntegralMethod::Trapezoid,
+        IntegralMethod::Simpson,
+        IntegralMethod::GaussKronrod,
+        IntegralMethod::Romberg,
+        IntegralMethod::TanhSinh,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntegralMethod::Trapezoid => "trapezoid",
+            IntegralMethod::Simpson => "simpson",
+            IntegralMethod::GaussKronrod => "gauss-kronrod",
+            IntegralMethod::Romberg => "romberg",
+            IntegralMethod::TanhSinh => "tanh-sinh",
+        }
+    }
+}
+
+impl Display for IntegralMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for IntegralMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        IntegralMethod::ALL
+            .into_iter()
+            .find(|method| method.name() == s)
+            .ok_or_else(|| format!("unknown integration method `{}`", s))
+    }
+}
+
+/// Finds definite integral of `f` from `x1` to `x2` with accuracy `eps`. The
+/// result is negated when `x1 > x2`, so integrals over adjacent intervals add
+/// up. Infinite limits are mapped onto a finite interval by substitution, the
+/// reported resolution is in the substituted variable then. `NaN` limits are
+/// reported as [`Termination::NonFinite`]
+pub fn integral(
+    f: impl Fn(f64) -> f64,
+    x1: f64,
+    x2: f64,
+    eps: f64,
+    max_iterations: Option<usize>,
+    method: IntegralMethod,
+) -> Report {
+    let max_iter = max_iterations.unwrap_or(INT_MAX_ITER);
+    let counter = Counter::new(f);
+    let f = |x| counter.call(x);
+    if x1.is_nan() || x2.is_nan() {
+        let report = Report::new(
+            Termination::NonFinite,
+            f64::NAN,
+            f64::INFINITY,
+            0,
+            Resolution::Step(f64::NAN),
+        );
+        return counter.finish(report);
+    }
+    if x1 == x2 {
+        return counter.finish(Report::new(
+            Termination::Converged,
+            0.0,
+            0.0,
+            0,
+            Resolution::Step(0.0),
+        ));
+    }
+    let (a, b) = if x1 > x2 { (x2, x1) } else { (x1, x2) };
+    // interval ends are mapped to infinity, the nearest points inside are
+    // taken instead so that closed rules get the limit of the integrand there
+    let (lo, hi) = (f64::EPSILON, 1.0 - f64::EPSILON);
+    let mut report = match (a.is_finite(), b.is_finite()) {
+        (true, true) => apply(method, f, a, b, eps, max_iter),
+        (true, false) => apply(
+            method,
+            |t: f64| {
+                let s = 1.0 - t.min(hi);
+                f(a + (1.0 - s) / s) / (s * s)
+            },
+            0.0,
+            1.0,
+            eps,
+            max_iter,
+        ),
+        (false, true) => apply(
+            method,
+            |t: f64| {
+                let t = t.max(lo);
+                f(b - (1.0 - t) / t) / (t * t)
+            },
+            0.0,
+            1.0,
+            eps,
+            max_iter,
+        ),
+        (false, false) => apply(
+            method,
+            |t: f64| {
+                let t = t.clamp(-hi, hi);
+                let d = 1.0 - t * t;
+                f(t / d) * (1.0 + t * t) / (d * d)
+            },
+            -1.0,
+            1.0,
+            eps,
+            max_iter,
+        ),
+    };
+    if x1 > x2 {
+        report.value = -report.value;
+    }
+    counter.finish(report)
+}
+
+fn apply(
+    method: IntegralMethod,
+    f: impl Fn(f64) -> f64,
+    a: f64,
+    b: f64,
+    eps: f64,
+    max_iter: usize,
+) -> Report {
+    match method {
+        IntegralMethod::Trapezoid => trapezoid(f, a, b, eps, max_iter),
+        IntegralMethod::Simpson => simpson(f, a, b, eps, max_iter),
+        IntegralMethod::GaussKronrod => gauss_kronrod(f, a, b, eps, max_iter),
+        IntegralMethod::Romberg => romberg(f, a, b, eps, max_iter),
+        IntegralMethod::TanhSinh => tanh_sinh(f, a, b, eps, max_iter),
+    }
+}
+
+fn trapezoid(f: impl Fn(f64) -> f64, x1: f64, x2: f64, eps: f64, max_iter: usize) -> Report {
+    let mut step = x2 - x1;
+    let mut sum = 0.5 * (f(x1) + f(x2));
+    // difference of the trapezoid and midpoint sums is six times the error
+    // of their average
+    let mut error = f64::INFINITY;
+    let mut termination = Termination::MaxIterations;
+    let mut iterations = 0;
+
+    let mut n = 1_usize;
+    while n < max_iter {
+        iterations += 1;
+        let mut xi = x1 + step * 0.5;
+        let mut inc = f(xi);
+        for _ in 1..n {
+            xi += step;
+            inc += f(xi);
+        }
+        error = step * f64::abs(sum - inc) / 6.0;
+        sum += inc;
+        step *= 0.5;
+        n <<= 1;
+        if error < eps {
+            termination = Termination::Converged;
+            break;
+        }
+    }
+    Report::new(
+        termination,
+        step * sum,
+        error,
+        iterations,
+        Resolution::Step(step),
+    )
+}
+
+/// Subinterval of adaptive Simpson's rule with function values at its ends and
+/// middle, the rule value and the tolerance it has to meet
+struct SimpsonSegment {
+    a: f64,
+    b: f64,
+    fa: f64,
+    fm: f64,
+    fb: f64,
+    whole: f64,
+    eps: f64,
+    /// Error estimate inherited from the parent segment
+    error: f64,
+}
+
+fn simpson(f: impl Fn(f64) -> f64, a: f64, b: f64, eps: f64, max_iter: usize) -> Report {
+    let rule = |a: f64, b: f64, fa: f64, fm: f64, fb: f64| (b - a) / 6.0 * (fa + 4.0 * fm + fb);
+    let (fa, fm, fb) = (f(a), f(0.5 * (a + b)), f(b));
+    let mut stack = vec![SimpsonSegment {
+        a,
+        b,
+        fa,
+        fm,
+        fb,
+        whole: rule(a, b, fa, fm, fb),
+        eps,
+        error: f64::INFINITY,
+    }];
+    let mut value = 0.0;
+    let mut error = 0.0;
+    let mut iterations = 0;
+    let mut min_width = b - a;
+    let mut termination = Termination::Converged;
+
+    while let Some(seg) = stack.pop() {
+        if iterations >= max_iter {
+            value += seg.whole;
+            error += seg.error;
+            termination = Termination::MaxIterations;
+            continue;
+        }
+        iterations += 1;
+        let m = 0.5 * (seg.a + seg.b);
+        let (lm, rm) = (0.5 * (seg.a + m), 0.5 * (m + seg.b));
+        let (flm, frm) = (f(lm), f(rm));
+        let left = rule(seg.a, m, seg.fa, flm, seg.fm);
+        let right = rule(m, seg.b, seg.fm, frm, seg.fb);
+        let delta = left + right - seg.whole;
+        // accept when precision is exhausted or the value is not finite as
+        // splitting would not help then
+        if delta.abs() <= 15.0 * seg.eps || lm <= seg.a || rm >= seg.b || !delta.is_finite() {
+            value += left + right + delta / 15.0;
+            error += delta.abs() / 15.0;
+            min_width = min_width.min(0.5 * (seg.b - seg.a));
+            continue;
+        }
+        let (half_eps, half_error) = (0.5 * seg.eps, delta.abs() / 30.0);
+        stack.push(SimpsonSegment {
+            a: m,
+            b: seg.b,
+            fa: seg.fm,
+            fm: frm,
+            fb: seg.fb,
+            whole: right,
+            eps: half_eps,
+            error: half_error,
+        });
+        stack.push(SimpsonSegment {
+            a: seg.a,
+            b: m,
+            fa: seg.fa,
+            fm: flm,
+            fb: seg.fm,
+            whole: left,
+            eps: half_eps,
+            error: half_error,
+        });
+    }
+    Report::new(
+        termination,
+        value,
+        error,
+        iterations,
+        Resolution::Step(min_width),
+    )
+}
+
+/// Kronrod nodes on `[-1, 1]`, positive half in decreasing order. Odd ones
+/// are the nodes of the Gauss rule
+const KRONROD_NODES: [f64; 8] = [
+    0.991_455_371_120_812_6,
+    0.949_107_912_342_758_5,
+    0.864_864_423_359_769_1,
+    0.741_531_185_599_394_4,
+    0.586_087_235_467_691_1,
+    0.405_845_151_377_397_2,
+    0.207_784_955_007_898_5,
+    0.0,
+];
+
+const KRONROD_WEIGHTS: [f64; 8] = [
+    0.022_935_322_010_529_22,
+    0.063_092_092_629_978_55,
+    0.104_790_010_322_250_18,
+    0.140_653_259_715_525_92,
+    0.169_004_726_639_267_9,
+    0.190_350_578_064_785_4,
+    0.204_432_940_075_298_9,
+    0.209_482_141_084_727_83,
+];
+
+const GAUSS_WEIGHTS: [f64; 4] = [
+    0.129_484_966_168_869_7,
+    0.279_705_391_489_276_7,
+    0.381_830_050_505_118_9,
+    0.417_959_183_673_469_4,
+];
+
+/// Subinterval of adaptive Gauss–Kronrod quadrature ordered by error estimate
+struct Segment {
+    a: f64,
+    b: f64,
+    value: f64,
+    error: f64,
+}
+
+impl Segment {
+    /// Applies G7–K15 pair, the Kronrod value is taken and the difference
+    /// with the Gauss one is the error estimate
+    fn new(f: &impl Fn(f64) -> f64, a: f64, b: f64) -> Self {
+        let c = 0.5 * (a + b);
+        let h = 0.5 * (b - a);
+        let fc = f(c);
+        let mut kronrod = fc * KRONROD_WEIGHTS[7];
+        let mut gauss = fc * GAUSS_WEIGHTS[3];
+        for j in 0..7 {
+            let x = h * KRONROD_NODES[j];
+            let sum = f(c - x) + f(c + x);
+            kronrod += KRONROD_WEIGHTS[j] * sum;
+            if j % 2 == 1 {
+                gauss += GAUSS_WEIGHTS[j / 2] * sum;
+            }
+        }
+        Self {
+            a,
+            b,
+            value: kronrod * h,
+            error: ((kronrod - gauss) * h).abs(),
+        }
+    }
+}
+
+impl PartialEq for Segment {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Segment {}
+
+impl PartialOrd for Segment {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Segment {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.error.total_cmp(&other.error)
+    }
+}
+
+fn gauss_kronrod(f: impl Fn(f64) -> f64, a: f64, b: f64, eps: f64, max_iter: usize) -> Report {
+    let first = Segment::new(&f, a, b);
+    let mut value = first.value;
+    let mut error = first.error;
+    let mut segments = BinaryHeap::from([first]);
+    let mut iterations = 0;
+    let mut termination = Termination::Converged;
+
+    while error > eps && value.is_finite() {
+        if iterations >= max_iter {
+            termination = Termination::MaxIterations;
+            break;
+        }
+        let worst = segments.peek().unwrap();
+        let m = 0.5 * (worst.a + worst.b);
+        if m <= worst.a || m >= worst.b {
+            // precision is exhausted on the worst subinterval
+            termination = Termination::MaxIterations;
+            break;
+        }
+        let worst = segments.pop().unwrap();
+        let left = Segment::new(&f, worst.a, m);
+        let right = Segment::new(&f, m, worst.b);
+        value += left.value + right.value - worst.value;
+        error += left.error + right.error - worst.error;
+        segments.push(left);
+        segments.push(right);
+        iterations += 1;
+    }
+    // running sums drift, add the segments up again
+    let value = segments.iter().map(|seg| seg.value).sum();
+    let error = segments.iter().map(|seg| seg.error).sum();
+    let min_width = segments
+        .iter()
+        .map(|seg| seg.b - seg.a)
+        .fold(f64::INFINITY, f64::min);
+    Report::new(
+        termination,
+        value,
+        error,
+        iterations,
+        Resolution::Step(min_width),
+    )
+}
+
+fn romberg(f: impl Fn(f64) -> f64, a: f64, b: f64, eps: f64, max_iter: usize) -> Report {
+    // extrapolation can agree by chance on the first rows
+    const MIN_ROWS: usize = 5;
+    let mut step = b - a;
+    let mut prev_row = vec![0.5 * step * (f(a) + f(b))];
+    let mut error = f64::INFINITY;
+    let mut termination = Termination::MaxIterations;
+    let mut iterations = 0;
+
+    let mut n = 1_usize;
+    while n < max_iter {
+        iterations += 1;
+        let sum: f64 = (0..n).map(|k| f(a + (k as f64 + 0.5) * step)).sum();
+        let mut row = vec![0.5 * (prev_row[0] + step * sum)];
+        let mut factor = 1.0;
+        for j in 1..=prev_row.len() {
+            factor *= 4.0;
+            row.push(row[j - 1] + (row[j - 1] - prev_row[j - 1]) / (factor - 1.0));
+        }
+        error = (row[row.len() - 1] - prev_row[prev_row.len() - 1]).abs();
+        prev_row = row;
+        step *= 0.5;
+        n <<= 1;
+        if !error.is_finite() {
+            break;
+        }
+        if error < eps && prev_row.len() >= MIN_ROWS {
+            termination = Termination::Converged;
+            break;
+        }
+    }
+    Report::new(
+        termination,
+        prev_row[prev_row.len() - 1],
+        error,
+        iterations,
+        Resolution::Step(step),
+    )
+}
+
+/// Sum of weighted values at nodes `t = k * h` for `k = start, start + stride,
+/// ...` on both sides of the middle, stops once nodes merge with interval ends
+fn tanh_sinh_sum(
+    f: &impl Fn(f64) -> f64,
+    a: f64,
+    b: f64,
+    h: f64,
+    start: usize,
+    stride: usize,
+) -> f64 {
+    use std::f64::consts::FRAC_PI_2;
+    let half = 0.5 * (b - a);
+    let mut sum = 0.0;
+    let mut k = start;
+    loop {
+        let t = k as f64 * h;
+        let u = FRAC_PI_2 * t.sinh();
+        let cosh_u = u.cosh();
+        // distance to the ends as `1 - tanh(u)` without cancellation
+        let delta = half * (-u).exp() / cosh_u;
+        let weight = FRAC_PI_2 * t.cosh() / (cosh_u * cosh_u);
+        let (left, right) = (a + delta, b - delta);
+        if left <= a || right >= b || weight == 0.0 {
+            return sum;
+        }
+        sum += weight * (f(left) + f(right));
+        k += stride;
+    }
+}
+
+fn tanh_sinh(f: impl Fn(f64) -> f64, a: f64, b: f64, eps: f64, max_iter: usize) -> Report {
+    use std::f64::consts::FRAC_PI_2;
+    // further halvings are below `f64` precision
+    const MAX_LEVELS: usize = 12;
+    const MIN_LEVELS: usize = 3;
+    let half = 0.5 * (b - a);
+    let mut h = 1.0;
+    let mut sum = FRAC_PI_2 * f(0.5 * (a + b)) + tanh_sinh_sum(&f, a, b, h, 1, 1);
+    let mut value = half * h * sum;
+    let mut error = f64::INFINITY;
+    let mut termination = Termination::MaxIterations;
+    let mut iterations = 0;
+
+    while iterations < max_iter.min(MAX_LEVELS) {
+        iterations += 1;
+        h *= 0.5;
+        sum += tanh_sinh_sum(&f, a, b, h, 1, 2);
+        let new_value = half * h * sum;
+        error = (new_value - value).abs();
+        value = new_value;
+        if !error.is_finite() {
+            break;
+        }
+        if error < eps && iterations >= MIN_LEVELS {
+            termination = Termination::Converged;
+            break;
+        }
+    }
+    Report::new(termination, value, error, iterations, Resolution::Step(h))
+}
@@ -0,0 +1,14 @@
+pub use derivative::derivative;
+pub use integral::{integral, IntegralMethod};
+pub use report::{Report, Resolution, Termination};
+pub use root::{muller, newton, root, RootMethod};
+pub use roots::{roots, Multiplicity, Root};
+
+mod derivative;
+mod integral;
+mod report;
+mod root;
+mod roots;
+
+#[cfg(test)]
+mod tests;
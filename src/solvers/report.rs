@@ -0,0 +1,165 @@
+use std::cell::Cell;
+use std::fmt::{self, Display};
+
+/// Why the solver stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Termination {
+    Converged,
+    MaxIterations,
+    /// Bracketing method got an interval without a sign change
+    NoSignChange,
+    /// Function returned `NaN` or infinity and the solver could not recover,
+    /// or the interval end is not finite
+    NonFinite,
+    /// Root finder converged to a point where the function is larger than
+    /// at the starting points, like a pole where it changes sign
+    LargeResidual,
+}
+
+impl Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Termination::Converged => "converged",
+            Termination::MaxIterations => "max iterations reached",
+            Termination::NoSignChange => "no sign change on the interval",
+            Termination::NonFinite => "non-finite value",
+            Termination::LargeResidual => "function is not small at the value, maybe a pole",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// Where the iterations stopped: the final bracket for bracketing methods,
+/// the last step size otherwise
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolution {
+    Bracket(f64, f64),
+    Step(f64),
+}
+
+/// Solver result with convergence diagnostics, the value is complex for
+/// [`muller`](super::muller)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Report<T = f64> {
+    /// Best estimate found, `NaN` if there is none
+    pub value: T,
+    /// Function at `value` for root finders, `None` for other solvers or if
+    /// there is no value
+    pub residual: Option<T>,
+    /// Estimated absolute error of `value`
+    pub error: f64,
+    pub iterations: usize,
+    /// Number of function evaluations
+    pub evaluations: usize,
+    pub resolution: Resolution,
+    pub termination: Termination,
+}
+
+impl<T: Copy> Report<T> {
+    pub(super) fn new(
+        termination: Termination,
+        value: T,
+        error: f64,
+        iterations: usize,
+        resolution: Resolution,
+    ) -> Self {
+        Self {
+            value,
+            residual: None,
+            error,
+            iterations,
+            evaluations: 0,
+            resolution,
+            termination,
+        }
+    }
+
+    pub(super) fn with_residual(mut self, residual: T) -> Self {
+        self.residual = Some(residual);
+        self
+    }
+
+    pub fn is_converged(&self) -> bool {
+        self.termination == Termination::Converged
+    }
+
+    /// The value if the solver converged
+    pub fn ok(&self) -> Option<T> {
+        self.is_converged().then_some(self.value)
+    }
+}
+
+impl<T: Display> Display for Report<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "value:       {}", self.value)?;
+        if let Some(residual) = &self.residual {
+            writeln!(f, "residual:    {}", residual)?;
+        }
+        writeln!(f, "error:       {:e}", self.error)?;
+        writeln!(f, "iterations:  {}", self.iterations)?;
+        writeln!(f, "evaluations: {}", self.evaluations)?;
+        match self.resolution {
+            Resolution::Bracket(a, b) => writeln!(f, "bracket:     [{}, {}]", a, b)?,
+            Resolution::Step(step) => writeln!(f, "step:        {:e}", step)?,
+        }
+        write!(f, "termination: {}", self.termination)
+    }
+}
+
+/// Wraps solved function counting its evaluations and non-finite values
+pub(super) struct Counter<F> {
+    f: F,
+    evaluations: Cell<usize>,
+    non_finite: Cell<bool>,
+    /// Largest `|f|` of the first two evaluations, at the starting points
+    start: Cell<f64>,
+}
+
+impl<F: Fn(f64) -> f64> Counter<F> {
+    pub(super) fn new(f: F) -> Self {
+        Self {
+            f,
+            evaluations: Cell::new(0),
+            non_finite: Cell::new(false),
+            start: Cell::new(0.0),
+        }
+    }
+
+    pub(super) fn call(&self, x: f64) -> f64 {
+        self.evaluations.set(self.evaluations.get() + 1);
+        let y = (self.f)(x);
+        if !y.is_finite() {
+            self.non_finite.set(true);
+        }
+        if self.evaluations.get() <= 2 {
+            self.start.set(self.start.get().max(y.abs()));
+        }
+        y
+    }
+
+    /// Fills in the evaluation count, failures caused by non-finite values
+    /// are reported as such
+    pub(super) fn finish(self, mut report: Report) -> Report {
+        report.evaluations = self.evaluations.get();
+        let failed = !report.value.is_finite()
+            || (self.non_finite.get() && report.termination == Termination::MaxIterations);
+        if failed && report.termination != Termination::NoSignChange {
+            report.termination = Termination::NonFinite;
+        }
+        report
+    }
+
+    /// [`Counter::finish`] for root finders, which also evaluates the
+    /// residual. Brackets closing on a pole converge too, so the value is
+    /// only a root if `|f|` there is no larger than at the starting points
+    pub(super) fn finish_root(self, mut report: Report) -> Report {
+        if report.value.is_finite() {
+            let residual = self.call(report.value);
+            report.residual = Some(residual);
+            if report.is_converged() && (residual.is_nan() || residual.abs() > self.start.get()) {
+                report.termination = Termination::LargeResidual;
+            }
+        }
+        self.finish(report)
+    }
+}
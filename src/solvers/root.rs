@@ -0,0 +1,491 @@
+use std::cell::Cell;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use super::report::{Counter, Report, Resolution, Termination::*};
+use crate::executor::Complex;
+
+const ROOT_MAX_ITER: usize = 100_000;
+
+/// Root-finding algorithm. Bracketing methods require a sign change of the
+/// function between the interval ends, secant and Newton–Raphson only use
+/// them as starting points
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RootMethod {
+    Bisection,
+    /// Plain regula falsi, slow when one of the ends gets stuck
+    FalsePosition,
+    /// Regula falsi which halves the value at the end retained twice in a row
+    Illinois,
+    /// Brent–Dekker: inverse quadratic interpolation guarded by bisection
+    #[default]
+    Brent,
+    Ridders,
+    Secant,
+    /// Newton–Raphson starting from the middle of the interval
+    Newton,
+}
+
+impl RootMethod {
+    pub const ALL: [RootMethod; 7] = [
+        RootMethod::Bisection,
+        RootMethod::FalsePosition,
+        RootMethod::Illinois,
+        RootMethod::Brent,
+        RootMethod::Ridders,
+        RootMethod::Secant,
+        RootMethod::Newton,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RootMethod::Bisection => "bisection",
+            RootMethod::FalsePosition => "false-position",
+            RootMethod::Illinois => "illinois",
+            RootMethod::Brent => "brent",
+            RootMethod::Ridders => "ridders",
+            RootMethod::Secant => "secant",
+            RootMethod::Newton => "newton",
+        }
+    }
+}
+
+impl Display for RootMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for RootMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RootMethod::ALL
+            .into_iter()
+            .find(|method| method.name() == s)
+            .ok_or_else(|| format!("unknown root-finding method `{}`", s))
+    }
+}
+
+/// Finds root of `f` between `x1` and `x2` with accuracy `eps`. Newton's
+/// method uses central difference derivative of `f`, see [`newton`] to
+/// provide exact one
+pub fn root(
+    f: impl Fn(f64) -> f64,
+    x1: f64,
+    x2: f64,
+    eps: f64,
+    max_iterations: Option<usize>,
+    method: RootMethod,
+) -> Report {
+    let max_iter = max_iterations.unwrap_or(ROOT_MAX_ITER);
+    let counter = Counter::new(f);
+    let f = |x| counter.call(x);
+    let report = match method {
+        RootMethod::Bisection => bisection(f, x1, x2, eps, max_iter),
+        RootMethod::FalsePosition => false_position(f, x1, x2, eps, max_iter, false),
+        RootMethod::Illinois => false_position(f, x1, x2, eps, max_iter, true),
+        RootMethod::Brent => brent(f, x1, x2, eps, max_iter),
+        RootMethod::Ridders => ridders(f, x1, x2, eps, max_iter),
+        RootMethod::Secant => secant(f, x1, x2, eps, max_iter),
+        RootMethod::Newton => {
+            let df = |x: f64| {
+                let h = f64::EPSILON.cbrt() * f64::max(1.0, x.abs());
+                (f(x + h) - f(x - h)) / (2.0 * h)
+            };
+            newton_steps(f, df, 0.5 * (x1 + x2), eps, max_iter)
+        }
+    };
+    counter.finish_root(report)
+}
+
+/// Newton–Raphson iterations from `x0` with derivative `df`. Only evaluations
+/// of `f` are counted
+pub fn newton(
+    f: impl Fn(f64) -> f64,
+    df: impl Fn(f64) -> f64,
+    x0: f64,
+    eps: f64,
+    max_iterations: Option<usize>,
+) -> Report {
+    let counter = Counter::new(f);
+    let report = newton_steps(
+        |x| counter.call(x),
+        df,
+        x0,
+        eps,
+        max_iterations.unwrap_or(ROOT_MAX_ITER),
+    );
+    counter.finish_root(report)
+}
+
+fn newton_steps(
+    f: impl Fn(f64) -> f64,
+    df: impl Fn(f64) -> f64,
+    x0: f64,
+    eps: f64,
+    max_iter: usize,
+) -> Report {
+    let mut x = x0;
+    let mut dx = f64::INFINITY;
+    for i in 0..max_iter {
+        let fx = f(x);
+        if fx == 0.0 {
+            return Report::new(Converged, x, 0.0, i, Resolution::Step(dx.abs()));
+        }
+        dx = fx / df(x);
+        if !dx.is_finite() {
+            return Report::new(NonFinite, x, dx.abs(), i + 1, Resolution::Step(dx.abs()));
+        }
+        x -= dx;
+        if dx.abs() < eps {
+            return Report::new(Converged, x, dx.abs(), i + 1, Resolution::Step(dx.abs()));
+        }
+    }
+    Report::new(
+        MaxIterations,
+        x,
+        dx.abs(),
+        max_iter,
+        Resolution::Step(dx.abs()),
+    )
+}
+
+/// Muller's method from `x1`, `x2` and their midpoint. The roots of the
+/// parabola through the last three points may be complex, so it finds complex
+/// roots of real functions too. As for [`root`], a value where `|f|` is
+/// larger than at the starting points is not taken as a root
+pub fn muller(
+    f: impl Fn(Complex) -> Complex,
+    x1: Complex,
+    x2: Complex,
+    eps: f64,
+    max_iterations: Option<usize>,
+) -> Report<Complex> {
+    let evaluations = Cell::new(0);
+    let f = |x| {
+        evaluations.set(evaluations.get() + 1);
+        f(x)
+    };
+    let mut report = muller_steps(
+        f,
+        [x1, x2, (x1 + x2) * 0.5],
+        eps,
+        max_iterations.unwrap_or(ROOT_MAX_ITER),
+    );
+    report.evaluations = evaluations.get();
+    report
+}
+
+fn muller_steps(
+    f: impl Fn(Complex) -> Complex,
+    [mut x0, mut x1, mut x2]: [Complex; 3],
+    eps: f64,
+    max_iter: usize,
+) -> Report<Complex> {
+    let (mut f0, mut f1, mut f2) = (f(x0), f(x1), f(x2));
+    let start = f0.abs().max(f1.abs()).max(f2.abs());
+    let report = |termination, x2, f2: Complex, error, iterations, dx| {
+        let large = f2.abs().is_nan() || f2.abs() > start;
+        let termination = match termination == Converged && large {
+            true => LargeResidual,
+            false => termination,
+        };
+        Report::new(termination, x2, error, iterations, Resolution::Step(dx)).with_residual(f2)
+    };
+    let mut dx = f64::INFINITY;
+    for i in 0..max_iter {
+        if f2 == Complex::default() {
+            return report(Converged, x2, f2, 0.0, i, dx);
+        }
+        // divided differences of the parabola
+        let (h1, h2) = (x1 - x0, x2 - x1);
+        let (d1, d2) = ((f1 - f0) / h1, (f2 - f1) / h2);
+        let a = (d2 - d1) / (h1 + h2);
+        let b = d2 + a * h2;
+        let root = (b * b - a * f2 * 4.0).sqrt();
+        // the larger denominator gives the root closer to `x2`
+        let den = match (b + root).abs() >= (b - root).abs() {
+            true => b + root,
+            false => b - root,
+        };
+        let step = -(f2 * 2.0) / den;
+        dx = step.abs();
+        if !dx.is_finite() {
+            return report(NonFinite, x2, f2, dx, i + 1, dx);
+        }
+        (x0, x1, x2) = (x1, x2, x2 + step);
+        (f0, f1, f2) = (f1, f2, f(x2));
+        if dx < eps {
+            return report(Converged, x2, f2, dx, i + 1, dx);
+        }
+    }
+    report(MaxIterations, x2, f2, dx, max_iter, dx)
+}
+
+/// Ordered interval ends with function values at them
+struct Bracket {
+    a: f64,
+    b: f64,
+    fa: f64,
+    fb: f64,
+}
+
+/// Evaluates `f` at interval ends, returns `Err` with the report if the answer
+/// is already known or there is no sign change
+fn bracket(f: &impl Fn(f64) -> f64, x1: f64, x2: f64) -> Result<Bracket, Report> {
+    let (a, b) = if x1 > x2 { (x2, x1) } else { (x1, x2) };
+    let (fa, fb) = (f(a), f(b));
+    let resolution = Resolution::Bracket(a, b);
+    if fa == 0.0 {
+        return Err(Report::new(Converged, a, 0.0, 0, resolution));
+    }
+    if fb == 0.0 {
+        return Err(Report::new(Converged, b, 0.0, 0, resolution));
+    }
+    match fa * fb < 0.0 {
+        true => Ok(Bracket { a, b, fa, fb }),
+        false => Err(Report::new(
+            NoSignChange,
+            f64::NAN,
+            f64::INFINITY,
+            0,
+            resolution,
+        )),
+    }
+}
+
+fn bisection(f: impl Fn(f64) -> f64, x1: f64, x2: f64, eps: f64, max_iter: usize) -> Report {
+    let Bracket {
+        mut a,
+        mut b,
+        mut fa,
+        ..
+    } = match bracket(&f, x1, x2) {
+        Ok(bracket) => bracket,
+        Err(report) => return report,
+    };
+    for i in 0..max_iter {
+        let m = 0.5 * (a + b);
+        if b - a < eps {
+            return Report::new(Converged, m, 0.5 * (b - a), i, Resolution::Bracket(a, b));
+        }
+        let fm = f(m);
+        if fm == 0.0 {
+            return Report::new(Converged, m, 0.0, i + 1, Resolution::Bracket(a, b));
+        }
+        if fa * fm < 0.0 {
+            b = m;
+        } else {
+            a = m;
+            fa = fm;
+        }
+    }
+    let m = 0.5 * (a + b);
+    Report::new(
+        MaxIterations,
+        m,
+        0.5 * (b - a),
+        max_iter,
+        Resolution::Bracket(a, b),
+    )
+}
+
+/// Regula falsi, with `illinois` the value at the end retained twice in a row
+/// is halved so that it does not get stuck
+fn false_position(
+    f: impl Fn(f64) -> f64,
+    x1: f64,
+    x2: f64,
+    eps: f64,
+    max_iter: usize,
+    illinois: bool,
+) -> Report {
+    let Bracket {
+        mut a,
+        mut b,
+        mut fa,
+        mut fb,
+    } = match bracket(&f, x1, x2) {
+        Ok(bracket) => bracket,
+        Err(report) => return report,
+    };
+    // which end was replaced last: -1 for `b`, 1 for `a`
+    let mut side = 0;
+    let mut prev = f64::NAN;
+    for i in 0..max_iter {
+        let c = (a * fb - b * fa) / (fb - fa);
+        let error = f64::min(b - a, (c - prev).abs());
+        if b - a < eps || (c - prev).abs() < 0.5 * eps {
+            return Report::new(Converged, c, error, i, Resolution::Bracket(a, b));
+        }
+        prev = c;
+        let fc = f(c);
+        if fc == 0.0 {
+            return Report::new(Converged, c, 0.0, i + 1, Resolution::Bracket(a, b));
+        }
+        if fc * fb > 0.0 {
+            b = c;
+            fb = fc;
+            if illinois && side == -1 {
+                fa *= 0.5;
+            }
+            side = -1;
+        } else {
+            a = c;
+            fa = fc;
+            if illinois && side == 1 {
+                fb *= 0.5;
+            }
+            side = 1;
+        }
+    }
+    Report::new(
+        MaxIterations,
+        prev,
+        b - a,
+        max_iter,
+        Resolution::Bracket(a, b),
+    )
+}
+
+/// Brent–Dekker method following `zeroin`
+fn brent(f: impl Fn(f64) -> f64, x1: f64, x2: f64, eps: f64, max_iter: usize) -> Report {
+    let Bracket {
+        mut a,
+        mut b,
+        mut fa,
+        mut fb,
+    } = match bracket(&f, x1, x2) {
+        Ok(bracket) => bracket,
+        Err(report) => return report,
+    };
+    let (mut c, mut fc) = (b, fb);
+    let mut d = b - a;
+    let mut e = d;
+    let ordered = |b: f64, c: f64| Resolution::Bracket(b.min(c), b.max(c));
+    for i in 0..max_iter {
+        if (fb > 0.0) == (fc > 0.0) {
+            c = a;
+            fc = fa;
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            a = b;
+            b = c;
+            c = a;
+            fa = fb;
+            fb = fc;
+            fc = fa;
+        }
+        let tol = 2.0 * f64::EPSILON * b.abs() + 0.5 * eps;
+        let m = 0.5 * (c - b);
+        if m.abs() <= tol || fb == 0.0 {
+            return Report::new(Converged, b, m.abs(), i, ordered(b, c));
+        }
+        if e.abs() >= tol && fa.abs() > fb.abs() {
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                (2.0 * m * s, 1.0 - s)
+            } else {
+                let q = fa / fc;
+                let r = fb / fc;
+                (
+                    s * (2.0 * m * q * (q - r) - (b - a) * (r - 1.0)),
+                    (q - 1.0) * (r - 1.0) * (s - 1.0),
+                )
+            };
+            if p > 0.0 {
+                q = -q;
+            }
+            p = p.abs();
+            if 2.0 * p < f64::min(3.0 * m * q - (tol * q).abs(), (e * q).abs()) {
+                e = d;
+                d = p / q;
+            } else {
+                d = m;
+                e = d;
+            }
+        } else {
+            d = m;
+            e = d;
+        }
+        a = b;
+        fa = fb;
+        b += if d.abs() > tol { d } else { tol.copysign(m) };
+        fb = f(b);
+    }
+    Report::new(
+        MaxIterations,
+        b,
+        0.5 * (c - b).abs(),
+        max_iter,
+        ordered(b, c),
+    )
+}
+
+fn ridders(f: impl Fn(f64) -> f64, x1: f64, x2: f64, eps: f64, max_iter: usize) -> Report {
+    let Bracket {
+        mut a,
+        mut b,
+        mut fa,
+        mut fb,
+    } = match bracket(&f, x1, x2) {
+        Ok(bracket) => bracket,
+        Err(report) => return report,
+    };
+    let ordered = |a: f64, b: f64| Resolution::Bracket(a.min(b), a.max(b));
+    let mut x = f64::NAN;
+    for i in 0..max_iter {
+        let m = 0.5 * (a + b);
+        let fm = f(m);
+        let s = (fm * fm - fa * fb).sqrt();
+        if s == 0.0 {
+            return Report::new(Converged, m, 0.5 * (b - a).abs(), i + 1, ordered(a, b));
+        }
+        let x_new = m + (m - a) * (fa - fb).signum() * fm / s;
+        if (x_new - x).abs() < eps {
+            return Report::new(Converged, x_new, (x_new - x).abs(), i + 1, ordered(a, b));
+        }
+        x = x_new;
+        let fx = f(x);
+        if fx == 0.0 {
+            return Report::new(Converged, x, 0.0, i + 1, ordered(a, b));
+        }
+        if fm * fx < 0.0 {
+            (a, fa, b, fb) = (m, fm, x, fx);
+        } else if fa * fx < 0.0 {
+            (b, fb) = (x, fx);
+        } else {
+            (a, fa) = (x, fx);
+        }
+        if (b - a).abs() < eps {
+            return Report::new(Converged, x, (b - a).abs(), i + 1, ordered(a, b));
+        }
+    }
+    Report::new(MaxIterations, x, (b - a).abs(), max_iter, ordered(a, b))
+}
+
+fn secant(f: impl Fn(f64) -> f64, x1: f64, x2: f64, eps: f64, max_iter: usize) -> Report {
+    let (mut x0, mut x1) = (x1, x2);
+    let (mut f0, mut f1) = (f(x0), f(x1));
+    let mut step = (x1 - x0).abs();
+    for i in 0..max_iter {
+        if f1 == 0.0 {
+            return Report::new(Converged, x1, 0.0, i, Resolution::Step(step));
+        }
+        let x2 = x1 - f1 * (x1 - x0) / (f1 - f0);
+        if !x2.is_finite() {
+            return Report::new(NonFinite, x1, step, i + 1, Resolution::Step(step));
+        }
+        step = (x2 - x1).abs();
+        if step < eps {
+            return Report::new(Converged, x2, step, i + 1, Resolution::Step(step));
+        }
+        (x0, f0) = (x1, f1);
+        x1 = x2;
+        f1 = f(x1);
+    }
+    Report::new(MaxIterations, x1, step, max_iter, Resolution::Step(step))
+}
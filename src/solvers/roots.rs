@@ -0,0 +1,146 @@
+use std::fmt::{self, Display};
+
+use super::root::{root, RootMethod};
+
+/// Whether the function changes sign at the root. Odd roots are found from
+/// sign changes, even ones from minima of `|f|` touching zero
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Multiplicity {
+    Odd,
+    Even,
+}
+
+impl Display for Multiplicity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Multiplicity::Odd => write!(f, "odd"),
+            Multiplicity::Even => write!(f, "even"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Root {
+    pub value: f64,
+    /// Function at `value`
+    pub residual: f64,
+    pub multiplicity: Multiplicity,
+}
+
+/// Finds all roots of `f` between `x1` and `x2` sampling it at `samples`
+/// equal steps. Every sign change is refined with Brent's method, sign
+/// changes at poles do not converge and are dropped. Local minima of `|f|`
+/// are refined with golden section search and taken as even roots if
+/// `|f| <= eps` there. Roots closer than `eps` are merged, the result is
+/// sorted. Roots closer than the sampling step may be missed
+pub fn roots(
+    f: impl Fn(f64) -> f64,
+    x1: f64,
+    x2: f64,
+    eps: f64,
+    samples: usize,
+    max_iterations: Option<usize>,
+) -> Vec<Root> {
+    let (a, b) = if x1 > x2 { (x2, x1) } else { (x1, x2) };
+    let samples = samples.max(1);
+    let step = (b - a) / samples as f64;
+    let xs: Vec<f64> = (0..=samples).map(|i| a + i as f64 * step).collect();
+    let fs: Vec<f64> = xs.iter().map(|&x| f(x)).collect();
+    let mut found = Vec::new();
+
+    for i in 0..=samples {
+        let (x, fx) = (xs[i], fs[i]);
+        let prev = i.checked_sub(1).map(|j| fs[j]);
+        let next = fs.get(i + 1).copied();
+        if fx == 0.0 {
+            let sign_change = matches!((prev, next), (Some(p), Some(n)) if p * n < 0.0);
+            let multiplicity = match sign_change || prev.is_none() || next.is_none() {
+                true => Multiplicity::Odd,
+                false => Multiplicity::Even,
+            };
+            found.push(Root {
+                value: x,
+                residual: fx,
+                multiplicity,
+            });
+            continue;
+        }
+        if let Some(next) = next {
+            if fx * next < 0.0 {
+                // sign changes at poles do not converge
+                let report = root(&f, x, xs[i + 1], eps, max_iterations, RootMethod::Brent);
+                if let (Some(value), Some(residual)) = (report.ok(), report.residual) {
+                    found.push(Root {
+                        value,
+                        residual,
+                        multiplicity: Multiplicity::Odd,
+                    });
+                }
+                continue;
+            }
+        }
+        let (Some(prev), Some(next)) = (prev, next) else {
+            continue;
+        };
+        let is_minimum = fx.abs() < prev.abs() && fx.abs() <= next.abs();
+        if is_minimum && prev * fx > 0.0 && fx * next > 0.0 {
+            let value = minimize_abs(&f, xs[i - 1], xs[i + 1], eps, max_iterations);
+            let residual = f(value);
+            if residual.abs() <= eps {
+                found.push(Root {
+                    value,
+                    residual,
+                    multiplicity: Multiplicity::Even,
+                });
+            }
+        }
+    }
+
+    found.sort_by(|r1, r2| r1.value.total_cmp(&r2.value));
+    let mut merged: Vec<Root> = Vec::with_capacity(found.len());
+    for found_root in found {
+        match merged.last_mut() {
+            Some(last) if found_root.value - last.value < eps => {
+                if found_root.multiplicity == Multiplicity::Odd {
+                    last.multiplicity = Multiplicity::Odd;
+                }
+            }
+            _ => merged.push(found_root),
+        }
+    }
+    merged
+}
+
+/// Golden section search for the minimum of `|f|` on `[a, b]`
+fn minimize_abs(
+    f: &impl Fn(f64) -> f64,
+    a: f64,
+    b: f64,
+    eps: f64,
+    max_iterations: Option<usize>,
+) -> f64 {
+    const ROOTS_MAX_ITER: usize = 100_000;
+    let ratio = 0.5 * (5.0_f64.sqrt() - 1.0);
+    let (mut a, mut b) = (a, b);
+    let mut c = b - ratio * (b - a);
+    let mut d = a + ratio * (b - a);
+    let (mut fc, mut fd) = (f(c).abs(), f(d).abs());
+    for _ in 0..max_iterations.unwrap_or(ROOTS_MAX_ITER) {
+        if b - a < eps || fc == 0.0 {
+            break;
+        }
+        if fc < fd {
+            (b, d, fd) = (d, c, fc);
+            c = b - ratio * (b - a);
+            fc = f(c).abs();
+        } else {
+            (a, c, fc) = (c, d, fd);
+            d = a + ratio * (b - a);
+            fd = f(d).abs();
+        }
+    }
+    match fc < fd {
+        true => c,
+        false => d,
+    }
+}
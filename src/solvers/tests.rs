@@ -0,0 +1,317 @@
+use super::*;
+
+#[test]
+fn it_works() {
+    let f = |x| f64::sin(x) - x.powi(3) + 3.0;
+
+    let eps = 0.000_000_1;
+
+    let r = root(f, 1.0, 2.0, eps, None, RootMethod::default())
+        .ok()
+        .unwrap();
+    let i = integral(f, -2.0, 2.0, eps, None, IntegralMethod::default())
+        .ok()
+        .unwrap();
+    let expected_r = 1.587_382_86;
+    let expected_i = 12.0;
+
+    assert!(f64::abs(r - expected_r) < eps);
+    assert!(f64::abs(i - expected_i) < eps);
+}
+
+/// Function, interval ends and the expected root or integral
+type RootCase = (fn(f64) -> f64, f64, f64, f64);
+
+#[test]
+fn root_methods() {
+    let eps = 0.000_000_001;
+    let cases: [RootCase; 4] = [
+        (|x| f64::sin(x) - x.powi(3) + 3.0, 1.0, 2.0, 1.587_382_860_3),
+        (|x| x.powi(10) - 0.5, 0.8, 1.2, 0.933_032_991_5),
+        (|x| f64::exp(x) - 10.0, 0.0, 4.0, std::f64::consts::LN_10),
+        (|x| f64::cos(x) - x, 1.0, 0.0, 0.739_085_133_2),
+    ];
+    for method in RootMethod::ALL {
+        for (f, x1, x2, expected) in cases {
+            let r = root(f, x1, x2, eps, None, method).ok().unwrap();
+            assert!(f64::abs(r - expected) < 0.000_001, "{:?}: {}", method, r);
+        }
+    }
+}
+
+#[test]
+fn no_sign_change() {
+    let f = |x: f64| x * x + 1.0;
+    for method in [
+        RootMethod::Bisection,
+        RootMethod::FalsePosition,
+        RootMethod::Illinois,
+        RootMethod::Brent,
+        RootMethod::Ridders,
+    ] {
+        let report = root(f, -1.0, 2.0, 0.000_001, None, method);
+        assert_eq!(report.termination, Termination::NoSignChange);
+        assert!(report.value.is_nan());
+    }
+}
+
+#[test]
+fn newton_with_derivative() {
+    let r = newton(|x| x * x - 2.0, |x| 2.0 * x, 1.0, 0.000_000_001, None)
+        .ok()
+        .unwrap();
+    assert!(f64::abs(r - std::f64::consts::SQRT_2) < 0.000_000_001);
+}
+
+#[test]
+fn reports() {
+    let f = |x: f64| x * x - 2.0;
+
+    let report = root(f, 0.0, 2.0, 0.000_001, None, RootMethod::Bisection);
+    assert_eq!(report.termination, Termination::Converged);
+    assert!(report.error < 0.000_001);
+    // the ends, the iterations and the residual
+    assert_eq!(report.evaluations, report.iterations + 3);
+    assert_eq!(report.residual, Some(f(report.value)));
+    let Resolution::Bracket(a, b) = report.resolution else {
+        panic!("bisection has to report a bracket");
+    };
+    assert!(a <= report.value && report.value <= b);
+
+    let report = root(f, 0.0, 2.0, 0.000_001, Some(3), RootMethod::Bisection);
+    assert_eq!(report.termination, Termination::MaxIterations);
+    assert_eq!(report.iterations, 3);
+    assert_eq!(report.ok(), None);
+
+    let report = root(
+        |x: f64| 1.0 / x,
+        1.0,
+        2.0,
+        0.000_001,
+        None,
+        RootMethod::Secant,
+    );
+    assert_eq!(report.termination, Termination::NonFinite);
+
+    // brackets closing on a pole do not converge
+    let report = root(
+        |x: f64| 1.0 / x,
+        -1.0,
+        2.0,
+        0.000_001,
+        None,
+        RootMethod::Bisection,
+    );
+    assert_eq!(report.termination, Termination::LargeResidual);
+    for method in [
+        RootMethod::Bisection,
+        RootMethod::FalsePosition,
+        RootMethod::Illinois,
+        RootMethod::Brent,
+        RootMethod::Ridders,
+    ] {
+        let report = root(|x: f64| 1.0 / x, -1.0, 2.0, 0.000_001, None, method);
+        assert!(!report.is_converged(), "{}", method);
+        assert!(report.residual.is_none_or(|r| r.abs() > 1.0), "{}", method);
+    }
+
+    let report = integral(f, 0.0, 3.0, 0.000_001, None, IntegralMethod::Trapezoid);
+    assert_eq!(report.termination, Termination::Converged);
+    assert_eq!(report.residual, None);
+    assert!(matches!(report.resolution, Resolution::Step(_)));
+    assert_eq!(report.evaluations, (1 << report.iterations) + 1);
+
+    let report = integral(
+        |x: f64| 1.0 / x,
+        0.0,
+        1.0,
+        0.000_001,
+        Some(8),
+        IntegralMethod::Trapezoid,
+    );
+    assert_eq!(report.termination, Termination::NonFinite);
+}
+
+#[test]
+fn integral_methods() {
+    let eps = 0.000_000_1;
+    let cases: [RootCase; 4] = [
+        (|x| f64::sin(x) - x.powi(3) + 3.0, -2.0, 2.0, 12.0),
+        (|x| f64::exp(-x * x), -5.0, 5.0, std::f64::consts::PI.sqrt()),
+        (
+            |x| 1.0 / (1.0 + 10_000.0 * x * x),
+            -1.0,
+            1.0,
+            0.02 * f64::atan(100.0),
+        ),
+        (f64::sqrt, 0.0, 1.0, 2.0 / 3.0),
+    ];
+    for method in IntegralMethod::ALL {
+        for (f, x1, x2, expected) in cases {
+            let report = integral(f, x1, x2, eps, None, method);
+            let i = report
+                .ok()
+                .unwrap_or_else(|| panic!("{:?}: {}", method, report));
+            assert!(f64::abs(i - expected) < 0.000_001, "{:?}: {}", method, i);
+        }
+    }
+}
+
+#[test]
+fn adaptive_integration_is_cheap() {
+    // derivative blows up at 0, so uniform refinement converges slowly
+    let f = f64::sqrt;
+    let eps = 0.000_000_01;
+    let trapezoid = integral(f, 0.0, 1.0, eps, None, IntegralMethod::Trapezoid);
+    for method in [IntegralMethod::Simpson, IntegralMethod::GaussKronrod] {
+        let report = integral(f, 0.0, 1.0, eps, None, method);
+        assert!(report.is_converged());
+        assert!(f64::abs(report.value - 2.0 / 3.0) < 0.000_000_1);
+        assert!(
+            report.evaluations * 10 < trapezoid.evaluations,
+            "{:?}",
+            method
+        );
+    }
+}
+
+#[test]
+fn integral_orientation() {
+    let f = |x: f64| x;
+    for method in IntegralMethod::ALL {
+        let forward = integral(f, 0.0, 1.0, 0.000_001, None, method);
+        let backward = integral(f, 1.0, 0.0, 0.000_001, None, method);
+        assert!(f64::abs(forward.value - 0.5) < 0.000_001, "{:?}", method);
+        assert!(f64::abs(backward.value + 0.5) < 0.000_001, "{:?}", method);
+        assert!(backward.is_converged());
+
+        let empty = integral(f, 2.0, 2.0, 0.000_001, None, method);
+        assert_eq!(empty.ok(), Some(0.0));
+        assert_eq!(empty.evaluations, 0);
+
+        for (x1, x2) in [(0.0, f64::NAN), (f64::NAN, f64::INFINITY)] {
+            let report = integral(f, x1, x2, 0.000_001, None, method);
+            assert_eq!(report.termination, Termination::NonFinite);
+            assert_eq!(report.evaluations, 0);
+        }
+    }
+}
+
+#[test]
+fn improper_integrals() {
+    use std::f64::consts::PI;
+    let eps = 0.000_000_1;
+    let cases: [RootCase; 4] = [
+        (|x| f64::exp(-x * x), 0.0, f64::INFINITY, PI.sqrt() / 2.0),
+        (f64::exp, f64::NEG_INFINITY, 0.0, 1.0),
+        (
+            |x| 1.0 / (1.0 + x * x),
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            PI,
+        ),
+        (|x| 1.0 / (1.0 + x * x), f64::INFINITY, 0.0, -PI / 2.0),
+    ];
+    for method in IntegralMethod::ALL {
+        for (f, x1, x2, expected) in cases {
+            let report = integral(f, x1, x2, eps, None, method);
+            assert!(
+                f64::abs(report.value - expected) < 0.000_001,
+                "{:?}: {}",
+                method,
+                report
+            );
+        }
+    }
+
+    // singular at both ends, closed rules would evaluate them
+    let f = |x: f64| 1.0 / f64::sqrt(x * (1.0 - x));
+    let report = integral(f, 0.0, 1.0, eps, None, IntegralMethod::TanhSinh);
+    assert!(report.is_converged());
+    assert!(f64::abs(report.value - PI) < 0.000_001, "{}", report);
+}
+
+#[test]
+fn all_roots() {
+    use std::f64::consts::PI;
+    let eps = 0.000_000_001;
+
+    // roots at 1 / (k * pi), k = 4..=31
+    let found = roots(|x| f64::sin(1.0 / x), 0.01, 0.1, eps, 10_000, None);
+    assert_eq!(found.len(), 28);
+    for (k, r) in (4..=31).rev().zip(&found) {
+        assert!(f64::abs(r.value - 1.0 / (k as f64 * PI)) < 0.000_001);
+        assert_eq!(r.multiplicity, Multiplicity::Odd);
+    }
+
+    let found = roots(|x| (x - 1.0).powi(2) * (x + 1.0), 2.0, -2.0, eps, 100, None);
+    assert_eq!(found.len(), 2);
+    assert!(f64::abs(found[0].value + 1.0) < 0.000_001);
+    assert_eq!(found[0].multiplicity, Multiplicity::Odd);
+    assert!(f64::abs(found[1].value - 1.0) < 0.000_001);
+    assert_eq!(found[1].multiplicity, Multiplicity::Even);
+
+    // roots at the sampled points are not duplicated, poles are dropped
+    let found = roots(f64::tan, -2.0, 2.0, eps, 4, None);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].value, 0.0);
+    assert_eq!(found[0].residual, 0.0);
+
+    assert!(roots(|x| x * x + 1.0, -1.0, 1.0, eps, 100, None).is_empty());
+}
+
+/// Function, point, order and the expected derivative
+type DerivativeCase = (fn(f64) -> f64, f64, usize, f64);
+
+#[test]
+fn numerical_derivatives() {
+    let eps = 0.000_001;
+    let cases: [DerivativeCase; 6] = [
+        (f64::sin, 1.0, 1, f64::cos(1.0)),
+        (f64::sin, 1.0, 2, -f64::sin(1.0)),
+        (f64::exp, 2.0, 3, f64::exp(2.0)),
+        (|x| x.powi(3), -100.0, 1, 30_000.0),
+        (f64::ln, 0.05, 1, 20.0),
+        (|x| x * x, 3.0, 0, 9.0),
+    ];
+    for (f, x, order, expected) in cases {
+        let report = derivative(f, x, order, eps, None);
+        assert_eq!(report.termination, Termination::Converged, "{}", report);
+        assert!(report.error <= eps);
+        assert!(
+            f64::abs(report.value - expected) < 10.0 * eps,
+            "order {} at {}: {}, expected {}",
+            order,
+            x,
+            report.value,
+            expected
+        );
+    }
+
+    // the initial step is shrunk to stay inside the domain
+    let report = derivative(f64::sqrt, 0.01, 1, eps, None);
+    assert!(f64::abs(report.value - 5.0) < 0.001, "{}", report);
+
+    let report = derivative(|x: f64| x.abs(), 0.0, 2, eps, None);
+    assert!(!report.is_converged());
+}
+
+#[test]
+fn complex_roots() {
+    use crate::executor::{Complex, I};
+
+    let f = |z: Complex| z * z + Complex::from(1.0);
+    let report = muller(f, Complex::from(0.0), Complex::from(2.0), 1e-12, None);
+    assert_eq!(report.termination, Termination::Converged);
+    assert!((report.value - I).abs() < 1e-12, "{}", report.value);
+    assert!(report.residual.unwrap().abs() < 1e-12);
+    assert!(report.evaluations > report.iterations);
+
+    // starting points below the real axis find the conjugate root
+    let report = muller(f, Complex::from(0.0), Complex::new(0.0, -2.0), 1e-12, None);
+    assert!((report.value + I).abs() < 1e-12, "{}", report.value);
+
+    let report = muller(f, Complex::from(0.0), Complex::from(2.0), 1e-12, Some(1));
+    assert_eq!(report.termination, Termination::MaxIterations);
+    assert_eq!(report.ok(), None);
+}
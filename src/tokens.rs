@@ -1,36 +1,41 @@
+use std::sync::Arc;
+
+use crate::ast::BoundOp;
+use crate::context::{Function, NativeFunction};
+
 #[cfg_attr(test, derive(PartialEq))]
-#[cfg_attr(debug_assertions, derive(Debug))]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub struct Token<'src> {
     pub pos: usize,
     pub text: &'src str,
-    pub token_type: TokenType,
+    pub token_type: TokenType<'src>,
 }
 
 #[cfg_attr(test, derive(PartialEq))]
-#[cfg_attr(debug_assertions, derive(Debug))]
-#[derive(Clone, Copy)]
-pub enum TokenType {
+#[derive(Clone, Copy, Debug)]
+pub enum TokenType<'src> {
     Number(f64),
-    Var,
+    Var(&'src str),
     Add,
     Sub,
     Mul,
     UM,
+    UP,
     Div,
     Pow,
     LParen,
     RParen,
     Comma,
     Const(f64),
-    Func {
-        args: usize,
-        func: fn(Vec<f64>) -> f64,
-    },
+    Func(&'src Arc<NativeFunction>),
+    /// Function defined in the context the source is analysed with
+    UserFunc(&'src Function),
+    /// Operator binding a variable, written like a function call
+    Bound(BoundOp),
 }
 
 impl<'src> Token<'src> {
-    pub fn new(pos: usize, text: &'src str, token_type: TokenType) -> Self {
+    pub fn new(pos: usize, text: &'src str, token_type: TokenType<'src>) -> Self {
         Self {
             pos,
             text,
@@ -38,3 +43,26 @@ impl<'src> Token<'src> {
         }
     }
 }
+
+/// Byte range of the source text covered by a token or an error
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub pos: usize,
+    pub len: usize,
+}
+
+impl Span {
+    pub fn new(pos: usize, len: usize) -> Self {
+        Self { pos, len }
+    }
+
+    pub fn end(&self) -> usize {
+        self.pos + self.len
+    }
+}
+
+impl Token<'_> {
+    pub fn span(&self) -> Span {
+        Span::new(self.pos, self.text.len())
+    }
+}
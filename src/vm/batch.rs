@@ -0,0 +1,186 @@
+use std::thread;
+
+use super::{Instr, Program, Reg};
+use crate::executor::errors::ExecutionError;
+use crate::executor::unknown_ident;
+
+/// Number of rows run together, every register holds a lane per row
+const LANES: usize = 64;
+
+type Lanes = [f64; LANES];
+
+/// Registers of the variables given as columns with their values
+type Bindings<'a> = Vec<(usize, &'a [f64])>;
+
+impl Program {
+    /// Evaluates the program for every row of `columns` into `out`. Variables
+    /// with a column take their value from it, the others are resolved with
+    /// `lookup` once. Rows which can not be computed, e.g. with `series` bounds
+    /// that are not integers, are NaN.
+    ///
+    /// Programs without bound operators run a block of rows per instruction
+    /// in loops the compiler vectorises.
+    ///
+    /// # Panics
+    ///
+    /// If a column is not as long as `out`
+    pub fn eval_batch<F>(
+        &self,
+        columns: &[(&str, &[f64])],
+        lookup: &F,
+        out: &mut [f64],
+    ) -> Result<(), ExecutionError>
+    where
+        F: Fn(&str) -> Option<f64> + ?Sized,
+    {
+        let (registers, bindings) = self.bind_columns(columns, lookup, out.len())?;
+        self.run_rows(&registers, &bindings, out);
+        Ok(())
+    }
+
+    /// Same as [`Program::eval_batch`] with the rows split between `threads`
+    /// threads
+    pub fn eval_batch_parallel<F>(
+        &self,
+        columns: &[(&str, &[f64])],
+        lookup: &F,
+        out: &mut [f64],
+        threads: usize,
+    ) -> Result<(), ExecutionError>
+    where
+        F: Fn(&str) -> Option<f64> + ?Sized,
+    {
+        let (registers, bindings) = self.bind_columns(columns, lookup, out.len())?;
+        // blocks of rows are not split between threads
+        let rows = out
+            .len()
+            .div_ceil(threads.max(1))
+            .next_multiple_of(LANES)
+            .max(LANES);
+        thread::scope(|scope| {
+            for (i, out) in out.chunks_mut(rows).enumerate() {
+                let offset = i * rows;
+                let bindings: Bindings = bindings
+                    .iter()
+                    .map(|&(reg, column)| (reg, &column[offset..offset + out.len()]))
+                    .collect();
+                let registers = &registers;
+                scope.spawn(move || self.run_rows(registers, &bindings, out));
+            }
+        });
+        Ok(())
+    }
+
+    /// Initial registers with the variables resolved by `lookup` set, and the
+    /// registers of the ones with a column
+    fn bind_columns<'a, F>(
+        &self,
+        columns: &[(&str, &'a [f64])],
+        lookup: &F,
+        rows: usize,
+    ) -> Result<(Vec<f64>, Bindings<'a>), ExecutionError>
+    where
+        F: Fn(&str) -> Option<f64> + ?Sized,
+    {
+        for (name, column) in columns {
+            assert_eq!(column.len(), rows, "length of column `{}`", name);
+        }
+        let mut registers = self.registers();
+        let mut bindings = Vec::new();
+        for (name, pos, reg) in &self.vars {
+            match columns.iter().find(|(column, _)| column == name) {
+                Some((_, column)) => bindings.push((*reg as usize, *column)),
+                None => {
+                    registers[*reg as usize] =
+                        lookup(name).ok_or_else(|| unknown_ident(*pos, name))?;
+                }
+            }
+        }
+        Ok((registers, bindings))
+    }
+
+    fn run_rows(&self, registers: &[f64], bindings: &Bindings, out: &mut [f64]) {
+        let is_straight = self.code.iter().all(|instr| {
+            !matches!(
+                instr,
+                Instr::CheckSteps { .. }
+                    | Instr::JumpIfAbove { .. }
+                    | Instr::Jump { .. }
+                    | Instr::Integrate { .. }
+            )
+        });
+        if !is_straight {
+            let mut registers = registers.to_vec();
+            for (row, value) in out.iter_mut().enumerate() {
+                for &(reg, column) in bindings {
+                    registers[reg] = column[row];
+                }
+                *value = self.run(&mut registers).unwrap_or(f64::NAN);
+            }
+            return;
+        }
+
+        let mut lanes: Vec<Lanes> = registers.iter().map(|&value| [value; LANES]).collect();
+        let mut args = Vec::new();
+        for (block, out) in out.chunks_mut(LANES).enumerate() {
+            let start = block * LANES;
+            for &(reg, column) in bindings {
+                lanes[reg][..out.len()].copy_from_slice(&column[start..start + out.len()]);
+            }
+            self.exec_lanes(&mut lanes, &mut args);
+            out.copy_from_slice(&lanes[self.result as usize][..out.len()]);
+        }
+    }
+
+    /// Runs straight line code on all lanes, `args` is the buffer for
+    /// function arguments
+    fn exec_lanes(&self, regs: &mut [Lanes], args: &mut Vec<f64>) {
+        // operands are copied out, so the loops do not alias and vectorise
+        fn unary(regs: &mut [Lanes], dst: Reg, src: Reg, f: impl Fn(f64) -> f64) {
+            let src = regs[src as usize];
+            let dst = &mut regs[dst as usize];
+            for i in 0..LANES {
+                dst[i] = f(src[i]);
+            }
+        }
+        fn binary(regs: &mut [Lanes], dst: Reg, lhs: Reg, rhs: Reg, f: impl Fn(f64, f64) -> f64) {
+            let (lhs, rhs) = (regs[lhs as usize], regs[rhs as usize]);
+            let dst = &mut regs[dst as usize];
+            for i in 0..LANES {
+                dst[i] = f(lhs[i], rhs[i]);
+            }
+        }
+
+        for instr in &self.code {
+            match *instr {
+                Instr::Move { dst, src } => regs[dst as usize] = regs[src as usize],
+                Instr::Neg { dst, src } => unary(regs, dst, src, |x| -x),
+                Instr::Add { dst, lhs, rhs } => binary(regs, dst, lhs, rhs, |x, y| x + y),
+                Instr::Sub { dst, lhs, rhs } => binary(regs, dst, lhs, rhs, |x, y| x - y),
+                Instr::Mul { dst, lhs, rhs } => binary(regs, dst, lhs, rhs, |x, y| x * y),
+                Instr::Div { dst, lhs, rhs } => binary(regs, dst, lhs, rhs, |x, y| x / y),
+                Instr::Pow { dst, lhs, rhs } => binary(regs, dst, lhs, rhs, f64::powf),
+                Instr::Call {
+                    dst,
+                    func,
+                    args: base,
+                    len,
+                } => {
+                    let func = &self.functions[func as usize];
+                    let mut values = [0.0; LANES];
+                    for (i, value) in values.iter_mut().enumerate() {
+                        args.clear();
+                        args.extend((base..base + len).map(|reg| regs[reg as usize][i]));
+                        *value = func.call(args);
+                    }
+                    regs[dst as usize] = values;
+                }
+                Instr::Increment { reg } => unary(regs, reg, reg, |x| x + 1.0),
+                Instr::CheckSteps { .. }
+                | Instr::JumpIfAbove { .. }
+                | Instr::Jump { .. }
+                | Instr::Integrate { .. } => unreachable!("control flow runs row by row"),
+            }
+        }
+    }
+}
@@ -0,0 +1,436 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::ast::{BinaryOp, BoundOp, Node, NodeKind, UnaryOp};
+use crate::context::NativeFunction;
+use crate::executor::errors::ExecutionError;
+use crate::executor::{check_steps, integrate, unknown_ident};
+
+mod batch;
+#[cfg(test)]
+mod tests;
+
+/// Index of a register
+type Reg = u32;
+
+/// Instruction of the register machine, jump targets are instruction indices
+#[derive(Clone, Copy, Debug)]
+enum Instr {
+    Move {
+        dst: Reg,
+        src: Reg,
+    },
+    Neg {
+        dst: Reg,
+        src: Reg,
+    },
+    Add {
+        dst: Reg,
+        lhs: Reg,
+        rhs: Reg,
+    },
+    Sub {
+        dst: Reg,
+        lhs: Reg,
+        rhs: Reg,
+    },
+    Mul {
+        dst: Reg,
+        lhs: Reg,
+        rhs: Reg,
+    },
+    Div {
+        dst: Reg,
+        lhs: Reg,
+        rhs: Reg,
+    },
+    Pow {
+        dst: Reg,
+        lhs: Reg,
+        rhs: Reg,
+    },
+    /// Calls function number `func` with the `len` registers from `args`
+    Call {
+        dst: Reg,
+        func: u32,
+        args: Reg,
+        len: u32,
+    },
+    /// Fails unless `from` and `to` are integers, `pos` is the source
+    /// position of the operator
+    CheckSteps {
+        op: BoundOp,
+        from: Reg,
+        to: Reg,
+        pos: u32,
+    },
+    JumpIfAbove {
+        lhs: Reg,
+        rhs: Reg,
+        target: u32,
+    },
+    Jump {
+        target: u32,
+    },
+    Increment {
+        reg: Reg,
+    },
+    /// Integrates `result` computed by the instructions up to `end` over
+    /// `var` from `from` to `to`, then continues at `end`
+    Integrate {
+        dst: Reg,
+        var: Reg,
+        from: Reg,
+        to: Reg,
+        result: Reg,
+        end: u32,
+        pos: u32,
+    },
+}
+
+/// Expression tree compiled for repeated evaluation. Every number, variable
+/// and intermediate value has its own register, so running the program does
+/// not allocate
+#[derive(Clone, Debug)]
+pub struct Program {
+    code: Vec<Instr>,
+    /// Initial values of the registers, the numbers are set in advance
+    registers: Vec<f64>,
+    /// Free variables with the source position of their first use
+    vars: Vec<(String, usize, Reg)>,
+    functions: Vec<Arc<NativeFunction>>,
+    result: Reg,
+}
+
+impl Program {
+    pub fn compile(node: &Node) -> Self {
+        let mut compiler = Compiler::default();
+        let result = compiler.compile(node, None);
+        Self {
+            code: compiler.code,
+            registers: compiler.registers,
+            vars: compiler.vars,
+            functions: compiler.functions,
+            result,
+        }
+    }
+
+    /// Free variables in the order of their first use with their registers
+    pub fn vars(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.vars
+            .iter()
+            .map(|(name, _, reg)| (name.as_str(), *reg as usize))
+    }
+
+    /// Register of free variable `name`
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        self.vars()
+            .find(|(var, _)| *var == name)
+            .map(|(_, reg)| reg)
+    }
+
+    /// Fails with the first free variable `lookup` does not resolve
+    pub fn check_vars<F>(&self, lookup: &F) -> Result<(), ExecutionError>
+    where
+        F: Fn(&str) -> Option<f64> + ?Sized,
+    {
+        match self.vars.iter().find(|(name, _, _)| lookup(name).is_none()) {
+            Some((name, pos, _)) => Err(unknown_ident(*pos, name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Registers to [`run`](Program::run) the program with, the variables
+    /// have to be set at their [`slot`](Program::slot)s
+    pub fn registers(&self) -> Vec<f64> {
+        self.registers.clone()
+    }
+
+    /// Runs the program on `registers` made by [`Program::registers`]. They
+    /// can be reused for the next run with other values of the variables
+    pub fn run(&self, registers: &mut [f64]) -> Result<f64, ExecutionError> {
+        self.exec(0, self.code.len(), registers)?;
+        Ok(registers[self.result as usize])
+    }
+
+    /// Evaluates the program resolving variables with `lookup`, the same as
+    /// [`executor::eval`](crate::executor::eval) does for the tree. It runs
+    /// on `registers` made by [`Program::registers`] like [`Program::run`]
+    pub fn eval<F>(&self, lookup: &F, registers: &mut [f64]) -> Result<f64, ExecutionError>
+    where
+        F: Fn(&str) -> Option<f64> + ?Sized,
+    {
+        for (name, pos, reg) in &self.vars {
+            registers[*reg as usize] = lookup(name).ok_or_else(|| unknown_ident(*pos, name))?;
+        }
+        self.run(registers)
+    }
+
+    /// Executes instructions from `pc` up to `end`
+    fn exec(&self, mut pc: usize, end: usize, regs: &mut [f64]) -> Result<(), ExecutionError> {
+        while pc < end {
+            match self.code[pc] {
+                Instr::Move { dst, src } => regs[dst as usize] = regs[src as usize],
+                Instr::Neg { dst, src } => regs[dst as usize] = -regs[src as usize],
+                Instr::Add { dst, lhs, rhs } => {
+                    regs[dst as usize] = regs[lhs as usize] + regs[rhs as usize]
+                }
+                Instr::Sub { dst, lhs, rhs } => {
+                    regs[dst as usize] = regs[lhs as usize] - regs[rhs as usize]
+                }
+                Instr::Mul { dst, lhs, rhs } => {
+                    regs[dst as usize] = regs[lhs as usize] * regs[rhs as usize]
+                }
+                Instr::Div { dst, lhs, rhs } => {
+                    regs[dst as usize] = regs[lhs as usize] / regs[rhs as usize]
+                }
+                Instr::Pow { dst, lhs, rhs } => {
+                    regs[dst as usize] = regs[lhs as usize].powf(regs[rhs as usize])
+                }
+                Instr::Call {
+                    dst,
+                    func,
+                    args,
+                    len,
+                } => {
+                    let args = &regs[args as usize..(args + len) as usize];
+                    regs[dst as usize] = self.functions[func as usize].call(args);
+                }
+                Instr::CheckSteps { op, from, to, pos } => {
+                    check_steps(pos as usize, op, regs[from as usize], regs[to as usize])?
+                }
+                Instr::JumpIfAbove { lhs, rhs, target } => {
+                    if regs[lhs as usize] > regs[rhs as usize] {
+                        pc = target as usize;
+                        continue;
+                    }
+                }
+                Instr::Jump { target } => {
+                    pc = target as usize;
+                    continue;
+                }
+                Instr::Increment { reg } => regs[reg as usize] += 1.0,
+                Instr::Integrate {
+                    dst,
+                    var,
+                    from,
+                    to,
+                    result,
+                    end,
+                    pos,
+                } => {
+                    let (from, to) = (regs[from as usize], regs[to as usize]);
+                    // the integrand runs on the same registers
+                    let body = RefCell::new(&mut *regs);
+                    let value = integrate(pos as usize, from, to, |t| {
+                        let mut regs = body.borrow_mut();
+                        regs[var as usize] = t;
+                        self.exec(pc + 1, end as usize, &mut regs)?;
+                        Ok(regs[result as usize])
+                    })?;
+                    regs[dst as usize] = value;
+                    pc = end as usize;
+                    continue;
+                }
+            }
+            pc += 1;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Compiler {
+    code: Vec<Instr>,
+    registers: Vec<f64>,
+    vars: Vec<(String, usize, Reg)>,
+    /// Variables bound by operators and shared subexpressions, innermost last
+    scopes: Vec<(String, Reg)>,
+    /// Registers of the numbers by their bits
+    numbers: HashMap<u64, Reg>,
+    functions: Vec<Arc<NativeFunction>>,
+}
+
+impl Compiler {
+    fn alloc(&mut self, value: f64) -> Reg {
+        self.registers.push(value);
+        (self.registers.len() - 1) as Reg
+    }
+
+    fn number(&mut self, value: f64) -> Reg {
+        if let Some(&reg) = self.numbers.get(&value.to_bits()) {
+            return reg;
+        }
+        let reg = self.alloc(value);
+        self.numbers.insert(value.to_bits(), reg);
+        reg
+    }
+
+    fn variable(&mut self, name: &str, pos: usize) -> Reg {
+        if let Some((_, reg)) = self.scopes.iter().rev().find(|(var, _)| var == name) {
+            return *reg;
+        }
+        if let Some((_, _, reg)) = self.vars.iter().find(|(var, ..)| var == name) {
+            return *reg;
+        }
+        let reg = self.alloc(f64::NAN);
+        self.vars.push((name.to_string(), pos, reg));
+        reg
+    }
+
+    fn function(&mut self, func: &Arc<NativeFunction>) -> u32 {
+        let index = match self.functions.iter().position(|f| Arc::ptr_eq(f, func)) {
+            Some(index) => index,
+            None => {
+                self.functions.push(func.clone());
+                self.functions.len() - 1
+            }
+        };
+        index as u32
+    }
+
+    fn here(&self) -> u32 {
+        self.code.len() as u32
+    }
+
+    /// Emits the code computing `node` into `dst` if given, returns the
+    /// register holding the value. Numbers and variables are not copied
+    /// unless `dst` is given
+    fn compile(&mut self, node: &Node, dst: Option<Reg>) -> Reg {
+        let src = match &node.kind {
+            NodeKind::Number(n) => Some(self.number(*n)),
+            NodeKind::Const { value, .. } => Some(self.number(*value)),
+            NodeKind::Var(name) => Some(self.variable(name, node.span.pos)),
+            _ => None,
+        };
+        if let Some(src) = src {
+            return match dst {
+                Some(dst) if dst != src => {
+                    self.code.push(Instr::Move { dst, src });
+                    dst
+                }
+                _ => src,
+            };
+        }
+
+        let dst = dst.unwrap_or_else(|| self.alloc(0.0));
+        let pos = node.span.pos as u32;
+        match &node.kind {
+            NodeKind::Unary {
+                op: UnaryOp::Neg,
+                operand,
+            } => {
+                let src = self.compile(operand, None);
+                self.code.push(Instr::Neg { dst, src });
+            }
+            NodeKind::Binary { op, lhs, rhs } => {
+                let (lhs, rhs) = (self.compile(lhs, None), self.compile(rhs, None));
+                self.code.push(match op {
+                    BinaryOp::Add => Instr::Add { dst, lhs, rhs },
+                    BinaryOp::Sub => Instr::Sub { dst, lhs, rhs },
+                    BinaryOp::Mul => Instr::Mul { dst, lhs, rhs },
+                    BinaryOp::Div => Instr::Div { dst, lhs, rhs },
+                    BinaryOp::Pow => Instr::Pow { dst, lhs, rhs },
+                });
+            }
+            NodeKind::Call { func, args, .. } => {
+                // arguments are passed in consecutive registers
+                let base = self.registers.len() as Reg;
+                for _ in args {
+                    self.alloc(0.0);
+                }
+                for (i, arg) in args.iter().enumerate() {
+                    self.compile(arg, Some(base + i as Reg));
+                }
+                let func = self.function(func);
+                let len = args.len() as u32;
+                self.code.push(Instr::Call {
+                    dst,
+                    func,
+                    args: base,
+                    len,
+                });
+            }
+            NodeKind::Bound {
+                op: op @ (BoundOp::Series | BoundOp::Prod),
+                var,
+                from,
+                to,
+                body,
+            } => {
+                let (from, to) = (self.compile(from, None), self.compile(to, None));
+                let op = *op;
+                self.code.push(Instr::CheckSteps { op, from, to, pos });
+                let init = self.number(if op == BoundOp::Series { 0.0 } else { 1.0 });
+                self.code.push(Instr::Move { dst, src: init });
+                let k = self.alloc(0.0);
+                self.code.push(Instr::Move { dst: k, src: from });
+
+                let start = self.here();
+                self.code.push(Instr::JumpIfAbove {
+                    lhs: k,
+                    rhs: to,
+                    target: 0,
+                });
+                self.scopes.push((var.clone(), k));
+                let value = self.compile(body, None);
+                self.scopes.pop();
+                self.code.push(match op {
+                    BoundOp::Series => Instr::Add {
+                        dst,
+                        lhs: dst,
+                        rhs: value,
+                    },
+                    _ => Instr::Mul {
+                        dst,
+                        lhs: dst,
+                        rhs: value,
+                    },
+                });
+                self.code.push(Instr::Increment { reg: k });
+                self.code.push(Instr::Jump { target: start });
+                let end = self.here();
+                if let Instr::JumpIfAbove { target, .. } = &mut self.code[start as usize] {
+                    *target = end;
+                }
+            }
+            NodeKind::Bound {
+                var,
+                from,
+                to,
+                body,
+                ..
+            } => {
+                let (from, to) = (self.compile(from, None), self.compile(to, None));
+                let t = self.alloc(0.0);
+                let start = self.here();
+                self.code.push(Instr::Integrate {
+                    dst,
+                    var: t,
+                    from,
+                    to,
+                    result: 0,
+                    end: 0,
+                    pos,
+                });
+                self.scopes.push((var.clone(), t));
+                let value = self.compile(body, None);
+                self.scopes.pop();
+                let here = self.here();
+                if let Instr::Integrate { result, end, .. } = &mut self.code[start as usize] {
+                    *result = value;
+                    *end = here;
+                }
+            }
+            NodeKind::Let { var, value, body } => {
+                let value = self.compile(value, None);
+                self.scopes.push((var.clone(), value));
+                self.compile(body, Some(dst));
+                self.scopes.pop();
+            }
+            NodeKind::Number(_) | NodeKind::Const { .. } | NodeKind::Var(_) => unreachable!(),
+        }
+        dst
+    }
+}
@@ -0,0 +1,161 @@
+use super::*;
+use crate::executor::eval;
+use crate::optimizer::optimize;
+use crate::solver_error::SolverError;
+use crate::{Context, Expression};
+
+fn parse_text(text: &str) -> Node {
+    Expression::parse(text).unwrap().ast().clone()
+}
+
+fn lookup(name: &str) -> Option<f64> {
+    match name {
+        "x" => Some(0.7),
+        "y" => Some(-1.5),
+        "n" => Some(5.0),
+        _ => None,
+    }
+}
+
+/// Compares the program with the tree walking interpreter
+#[test]
+fn agrees_with_executor() {
+    for text in [
+        "2 + 2*sin(3^-3)",
+        "x^2 - 2*x + 1",
+        "-x*y + -(-x)",
+        "max(1, min(x, y), hypot(x, y, 2)) + atan2(y, x)",
+        "sum(x, y, 3, x*y, 5) + mean(x, y)",
+        "clamp(x^2, y, 1) + pi*e",
+        "median(x, y, 1) + median(x, y, x*y, 2, 2)",
+        "series(k, 1, n, x^k/k)",
+        "prod(k, 1, n, 1 + x/k) - prod(k, 3, 1, k)",
+        "series(k, 1, 3, series(j, 1, k, j*k))",
+        "series(x, 1, 3, x) + x",
+        "sum(x, 1, 3, x) + x",
+        "int(t, 0, x, exp(-t^2))",
+        "int(t, y, x, int(s, 0, t, s*t)) + x",
+        "sin(x)^2 + sin(x)^2*cos(x) + exp(sin(x)^2)",
+        "series(k, 1, n, (x + k)^2 + sqrt(x + k))",
+    ] {
+        let node = optimize(parse_text(text));
+        let program = Program::compile(&node);
+        let expected = eval(&node, &lookup).unwrap();
+        let value = program.eval(&lookup, &mut program.registers()).unwrap();
+        assert!(
+            f64::abs(value - expected) <= 1e-12 * expected.abs().max(1.0),
+            "{}: {}, expected {}",
+            text,
+            value,
+            expected
+        );
+    }
+}
+
+#[test]
+fn registers_are_reused() {
+    let program = Program::compile(&parse_text("a*x^2 + b*x"));
+    let names: Vec<_> = program.vars().map(|(name, _)| name).collect();
+    assert_eq!(names, ["a", "x", "b"]);
+
+    let mut registers = program.registers();
+    registers[program.slot("a").unwrap()] = 2.0;
+    registers[program.slot("b").unwrap()] = 1.0;
+    let x = program.slot("x").unwrap();
+    for (value, expected) in [(1.0, 3.0), (2.0, 10.0), (-1.0, 1.0)] {
+        registers[x] = value;
+        assert_eq!(program.run(&mut registers).unwrap(), expected);
+    }
+    assert_eq!(program.slot("c"), None);
+}
+
+#[test]
+fn registered_functions() {
+    let mut context = Context::new();
+    context
+        .register_function("twice", crate::context::Arity::Fixed(1), |args| {
+            2.0 * args[0]
+        })
+        .unwrap();
+    let expr = Expression::parse_with("twice(x) + twice(twice(1))", &context).unwrap();
+    let program = Program::compile(expr.ast());
+    assert_eq!(
+        program.eval(&lookup, &mut program.registers()).unwrap(),
+        5.4
+    );
+}
+
+#[test]
+fn errors() {
+    for (text, code, pos) in [
+        ("x + z", "E301", 4),
+        ("series(k, 1, 2.5, k)", "E303", 0),
+        ("1 + prod(k, x, 3, k)", "E303", 4),
+        ("int(t, 0, 1, t*z)", "E301", 15),
+    ] {
+        let program = Program::compile(&parse_text(text));
+        let error = program.eval(&lookup, &mut program.registers()).unwrap_err();
+        assert_eq!(error.code(), code, "{}", text);
+        assert_eq!(error.span().pos, pos, "{}", text);
+    }
+}
+
+#[test]
+fn batches() {
+    let xs: Vec<f64> = (0..300).map(|i| i as f64 / 37.0 - 3.0).collect();
+    let ys: Vec<f64> = xs.iter().map(|x| x * x).collect();
+    for text in [
+        "a*x^2 - y + max(x, y, 1)",
+        "-x/y + sqrt(y) + hypot(x, a)",
+        "series(k, 1, 3, x^k) + y",
+        "series(k, 1, x, k)",
+    ] {
+        let program = Program::compile(&optimize(parse_text(text)));
+        let columns: [(&str, &[f64]); 2] = [("x", &xs), ("y", &ys)];
+        let lookup = |name: &str| (name == "a").then_some(2.0);
+        let mut out = vec![0.0; xs.len()];
+        // reused for every row
+        let mut registers = program.registers();
+        program.eval_batch(&columns, &lookup, &mut out).unwrap();
+        for (row, value) in out.iter().enumerate() {
+            let expected = program
+                .eval(
+                    &|name| match name {
+                        "x" => Some(xs[row]),
+                        "y" => Some(ys[row]),
+                        _ => lookup(name),
+                    },
+                    &mut registers,
+                )
+                .unwrap_or(f64::NAN);
+            assert!(
+                value.to_bits() == expected.to_bits(),
+                "{} at row {}: {}, expected {}",
+                text,
+                row,
+                value,
+                expected
+            );
+        }
+
+        for threads in [2, 3, 16] {
+            let mut parallel = vec![0.0; xs.len()];
+            program
+                .eval_batch_parallel(&columns, &lookup, &mut parallel, threads)
+                .unwrap();
+            assert_eq!(
+                parallel.iter().map(|v| v.to_bits()).collect::<Vec<_>>(),
+                out.iter().map(|v| v.to_bits()).collect::<Vec<_>>(),
+                "{} on {} threads",
+                text,
+                threads
+            );
+        }
+    }
+
+    let program = Program::compile(&parse_text("x + z"));
+    let error = program
+        .eval_batch(&[("x", &xs)], &lookup, &mut vec![0.0; xs.len()])
+        .unwrap_err();
+    assert_eq!((error.code(), error.span().pos), ("E301", 4));
+}